@@ -0,0 +1,554 @@
+//! Derive macros for the glass library.
+//! `#[derive(Sortable)]` generates the Sortable implementation of an object from its fields.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments,
+    Type,
+};
+
+/// Derives the Sortable trait for a struct with named fields.
+///
+/// The struct takes `#[glass(index = "mods")]` and optionally `none_encoding = "NA"`.
+/// Fields of type `Option<String>` are stored through the NoneEncoding, fields marked with
+/// `#[glass(json)]` are stored as JSON and every other field is converted with `From<String>`.
+/// `#[glass(type_field)]`, `#[glass(name_field)]`, `#[glass(tags)]` and
+/// `#[glass(dependencies)]` mark the fields returned by `type_field`, `name_field`,
+/// `tag_values` and `dependency_keys`. A map field marked `#[glass(extra)]` is stored as one field
+/// per entry, keyed with `EXTRA_PREFIX`, and left out of `fields`. When any field is marked
+/// `#[glass(public)]`, `public_fields` only returns the marked fields,
+/// otherwise every field is public. The struct attribute
+/// `normalize` forwards `Sortable::normalize` to an inherent `normalize` method,
+/// `validate` forwards `Sortable::validate` to an inherent `validate` method whose error
+/// converts into GlassError,
+/// `schema_version = 2` sets `SCHEMA_VERSION` and `migrate` forwards `Sortable::migrate_object`
+/// to an inherent `migrate_object` function. `codec = "path::To::Codec"` picks the FieldCodec
+/// that decides the DataType and how `#[glass(json)]` fields are written, defaulting to JSON.
+#[proc_macro_derive(Sortable, attributes(glass))]
+pub fn derive_sortable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How a field is written into the field map.
+enum FieldKind {
+    /// An `Option<String>` stored through the NoneEncoding.
+    Optional,
+    /// A field stored as JSON.
+    Json,
+    /// A field converted to and from a String.
+    Plain,
+    /// A map whose entries are stored as separate fields.
+    Extra,
+}
+
+/// A struct field along with its glass attributes.
+struct GlassField {
+    ident: Ident,
+    kind: FieldKind,
+    type_field: bool,
+    name_field: bool,
+    tags: bool,
+    dependencies: bool,
+    public: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+
+    let mut index: Option<LitStr> = None;
+    let mut none_encoding: Option<Ident> = None;
+    let mut normalize = false;
+    let mut validate = false;
+    let mut schema_version: Option<syn::LitInt> = None;
+    let mut migrate = false;
+    let mut codec: Option<syn::Path> = None;
+
+    for attr in input.attrs.iter().filter(|x| x.path().is_ident("glass")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("index") {
+                index = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("none_encoding") {
+                let value: LitStr = meta.value()?.parse()?;
+                none_encoding = Some(value.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("normalize") {
+                normalize = true;
+                Ok(())
+            } else if meta.path.is_ident("validate") {
+                validate = true;
+                Ok(())
+            } else if meta.path.is_ident("schema_version") {
+                schema_version = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("migrate") {
+                migrate = true;
+                Ok(())
+            } else if meta.path.is_ident("codec") {
+                let value: LitStr = meta.value()?.parse()?;
+                codec = Some(value.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown glass attribute"))
+            }
+        })?;
+    }
+
+    let index = index.ok_or_else(|| {
+        syn::Error::new_spanned(
+            name,
+            "Sortable requires a #[glass(index = \"...\")] attribute",
+        )
+    })?;
+
+    let named = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "Sortable can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "Sortable can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut fields = Vec::new();
+
+    for field in named {
+        let mut glass_field = GlassField {
+            ident: field.ident.clone().expect("named field"),
+            kind: if is_option_string(&field.ty) {
+                FieldKind::Optional
+            } else {
+                FieldKind::Plain
+            },
+            type_field: false,
+            name_field: false,
+            tags: false,
+            dependencies: false,
+            public: false,
+        };
+
+        for attr in field.attrs.iter().filter(|x| x.path().is_ident("glass")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("json") {
+                    glass_field.kind = FieldKind::Json;
+                } else if meta.path.is_ident("extra") {
+                    glass_field.kind = FieldKind::Extra;
+                } else if meta.path.is_ident("type_field") {
+                    glass_field.type_field = true;
+                } else if meta.path.is_ident("name_field") {
+                    glass_field.name_field = true;
+                } else if meta.path.is_ident("tags") {
+                    glass_field.tags = true;
+                } else if meta.path.is_ident("dependencies") {
+                    glass_field.dependencies = true;
+                } else if meta.path.is_ident("public") {
+                    glass_field.public = true;
+                } else {
+                    return Err(meta.error("unknown glass attribute"));
+                }
+                Ok(())
+            })?;
+        }
+
+        fields.push(glass_field);
+    }
+
+    let names: Vec<String> = fields
+        .iter()
+        .filter(|x| !matches!(x.kind, FieldKind::Extra))
+        .map(|x| x.ident.to_string())
+        .collect();
+
+    let none_encoding = none_encoding.map(|x| {
+        quote! {
+            const NONE_ENCODING: ::glass::objects::NoneEncoding =
+                ::glass::objects::NoneEncoding::#x;
+        }
+    });
+
+    let type_field = fields.iter().find(|x| x.type_field).map(|x| {
+        let field = x.ident.to_string();
+        quote! {
+            fn type_field() -> Option<&'static str> {
+                Some(#field)
+            }
+        }
+    });
+
+    let name_field = fields.iter().find(|x| x.name_field).map(|x| {
+        let field = x.ident.to_string();
+        quote! {
+            fn name_field() -> Option<&'static str> {
+                Some(#field)
+            }
+        }
+    });
+
+    let tag_values = fields.iter().find(|x| x.tags).map(|x| {
+        let field = &x.ident;
+        quote! {
+            fn tag_values(&self) -> Vec<String> {
+                self.#field.clone().unwrap_or_default()
+            }
+        }
+    });
+
+    let dependency_keys = fields.iter().find(|x| x.dependencies).map(|x| {
+        let field = &x.ident;
+        quote! {
+            fn dependency_keys(&self) -> Vec<::glass::__private::uuid::Uuid> {
+                self.#field.iter().flatten().map(|x| x.0).collect()
+            }
+        }
+    });
+
+    let public: Vec<String> = fields
+        .iter()
+        .filter(|x| x.public)
+        .map(|x| x.ident.to_string())
+        .collect();
+    let public_fields = if public.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn public_fields() -> Option<&'static [&'static str]> {
+                Some(&[#(#public),*])
+            }
+        })
+    };
+
+    let normalize = if normalize {
+        Some(quote! {
+            fn normalize(&mut self) -> Result<(), ::glass::error::GlassError> {
+                #name::normalize(self)
+            }
+        })
+    } else {
+        None
+    };
+
+    let validate = if validate {
+        Some(quote! {
+            fn validate(&self) -> Result<(), ::glass::error::GlassError> {
+                #name::validate(self).map_err(::std::convert::Into::into)
+            }
+        })
+    } else {
+        None
+    };
+
+    let schema_version = schema_version.map(|x| {
+        quote! {
+            const SCHEMA_VERSION: u32 = #x;
+        }
+    });
+
+    let migrate = if migrate {
+        Some(quote! {
+            fn migrate_object(
+                map: &mut ::std::collections::HashMap<String, Self::DataType>,
+                from_version: u32,
+            ) -> Result<(), ::glass::error::GlassError> {
+                #name::migrate_object(map, from_version)
+            }
+        })
+    } else {
+        None
+    };
+
+    let codec = match codec {
+        Some(path) => quote! { #path },
+        None => quote! { ::glass::objects::JsonCodec },
+    };
+
+    let decode = fields.iter().map(|x| {
+        let field = &x.ident;
+        let key = field.to_string();
+        match x.kind {
+            FieldKind::Optional => quote! { #field: fetch_value(#key) },
+            FieldKind::Json => quote! {
+                #field: map
+                    .get(#key)
+                    .and_then(<#codec as ::glass::objects::FieldCodec>::decode_value)
+                    .unwrap_or_default()
+            },
+            FieldKind::Plain => quote! {
+                #field: ::std::convert::From::from(collapse_string(fetch_value(#key)))
+            },
+            FieldKind::Extra => quote! {
+                #field: {
+                    let extra: Vec<_> = map
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            let key = key.strip_prefix(::glass::objects::EXTRA_PREFIX)?;
+                            <#codec as ::glass::objects::FieldCodec>::decode_value(value)
+                                .map(|x| (key.to_owned(), x))
+                        })
+                        .collect();
+
+                    if extra.is_empty() {
+                        None
+                    } else {
+                        Some(extra.into_iter().collect())
+                    }
+                }
+            },
+        }
+    });
+
+    let try_decode = fields.iter().map(|x| {
+        let field = &x.ident;
+        let key = field.to_string();
+        match x.kind {
+            FieldKind::Optional => quote! { #field: fetch_value(#key)? },
+            FieldKind::Json => quote! {
+                #field: match map.get(#key) {
+                    Some(value) => <#codec as ::glass::objects::FieldCodec>::decode_value(value)
+                        .ok_or_else(|| unreadable(#key))?,
+                    None => Default::default(),
+                }
+            },
+            FieldKind::Plain => quote! {
+                #field: ::std::convert::From::from(fetch_value(#key)?.unwrap_or_default())
+            },
+            FieldKind::Extra => quote! {
+                #field: {
+                    let extra = map
+                        .iter()
+                        .filter_map(|(key, value)| {
+                            let name = key.strip_prefix(::glass::objects::EXTRA_PREFIX)?;
+                            Some(
+                                <#codec as ::glass::objects::FieldCodec>::decode_value(value)
+                                    .map(|x| (name.to_owned(), x))
+                                    .ok_or_else(|| unreadable(key)),
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    if extra.is_empty() {
+                        None
+                    } else {
+                        Some(extra.into_iter().collect())
+                    }
+                }
+            },
+        }
+    });
+
+    let encode = fields
+        .iter()
+        .filter(|x| !matches!(x.kind, FieldKind::Extra))
+        .map(|x| {
+            let field = &x.ident;
+            let key = field.to_string();
+            match x.kind {
+                FieldKind::Optional => quote! {
+                    (#key, encoding.encode(&self.#field).map(encode_text))
+                },
+                FieldKind::Json => quote! {
+                    (
+                        #key,
+                        <#codec as ::glass::objects::FieldCodec>::encode_value(&self.#field)
+                            .or_else(|| Some(encode_text(String::new())))
+                    )
+                },
+                FieldKind::Plain => quote! {
+                    (#key, Some(encode_text(String::from(self.#field.clone()))))
+                },
+                FieldKind::Extra => unreachable!("extra fields are encoded separately"),
+            }
+        });
+
+    let encode_extra = fields
+        .iter()
+        .filter(|x| matches!(x.kind, FieldKind::Extra))
+        .map(|x| {
+            let field = &x.ident;
+            quote! {
+                .chain(self.#field.iter().flatten().map(|(key, value)| {
+                    (
+                        format!("{}{}", ::glass::objects::EXTRA_PREFIX, key),
+                        <#codec as ::glass::objects::FieldCodec>::encode_value(value),
+                    )
+                }))
+            }
+        });
+
+    Ok(quote! {
+        impl ::glass::objects::Sortable for #name {
+            type DataType = <#codec as ::glass::objects::FieldCodec>::DataType;
+
+            #none_encoding
+
+            #schema_version
+
+            fn object_to_index() -> &'static str {
+                #index
+            }
+
+            fn fields() -> &'static [&'static str] {
+                &[#(#names),*]
+            }
+
+            #public_fields
+
+            #type_field
+
+            #name_field
+
+            #tag_values
+
+            #dependency_keys
+
+            #normalize
+            #validate
+
+            #migrate
+
+            ::glass::__with_field_maps! {
+                fn map_to_object(map: ::std::collections::HashMap<String, Self::DataType>) -> Self {
+                    Self::from_encoded_map(map, Self::NONE_ENCODING)
+                }
+
+                fn try_map_to_object(
+                    map: ::std::collections::HashMap<String, Self::DataType>,
+                ) -> Result<Self, ::glass::error::GlassError> {
+                    Self::try_from_encoded_map(map, Self::NONE_ENCODING)
+                }
+
+                fn object_to_map(&self) -> Vec<(String, Self::DataType)> {
+                    ::glass::objects::order_fields(
+                        self.to_encoded_map(Self::NONE_ENCODING),
+                        <Self as ::glass::objects::Sortable>::map_field_order(),
+                    )
+                }
+            }
+        }
+
+        ::glass::__with_field_maps! {
+        impl #name {
+            /// Builds the object from a field map written with the given NoneEncoding.
+            fn from_encoded_map(
+                map: ::std::collections::HashMap<
+                    String,
+                    <Self as ::glass::objects::Sortable>::DataType,
+                >,
+                encoding: ::glass::objects::NoneEncoding,
+            ) -> Self {
+                let fetch_value = |key: &str| -> Option<String> {
+                    encoding.decode(
+                        map.get(key)
+                            .cloned()
+                            .and_then(<#codec as ::glass::objects::FieldCodec>::decode_text),
+                    )
+                };
+                let collapse_string = |x: Option<String>| -> String { x.unwrap_or_default() };
+
+                if map.is_empty() {
+                    Self {
+                        ..Default::default()
+                    }
+                } else {
+                    Self {
+                        #(#decode),*
+                    }
+                }
+            }
+
+            /// Builds the object from a field map written with the given NoneEncoding,
+            /// failing on the first field holding a value that can't be read.
+            fn try_from_encoded_map(
+                map: ::std::collections::HashMap<
+                    String,
+                    <Self as ::glass::objects::Sortable>::DataType,
+                >,
+                encoding: ::glass::objects::NoneEncoding,
+            ) -> Result<Self, ::glass::error::GlassError> {
+                let unreadable = |key: &str| {
+                    ::glass::error::GlassError::Deserialization(
+                        format!(
+                            "field {} of {} can't be read",
+                            key,
+                            <Self as ::glass::objects::Sortable>::object_to_index()
+                        )
+                        .into(),
+                    )
+                };
+                let fetch_value = |key: &str| {
+                    match map.get(key) {
+                        Some(value) => {
+                            <#codec as ::glass::objects::FieldCodec>::decode_text(value.clone())
+                                .map(|x| encoding.decode(Some(x)))
+                                .ok_or_else(|| unreadable(key))
+                        }
+                        None => Ok(None),
+                    }
+                };
+
+                if map.is_empty() {
+                    Ok(Self {
+                        ..Default::default()
+                    })
+                } else {
+                    Ok(Self {
+                        #(#try_decode),*
+                    })
+                }
+            }
+
+            /// Builds a field map from the object, writing missing values with the given NoneEncoding.
+            fn to_encoded_map(
+                &self,
+                encoding: ::glass::objects::NoneEncoding,
+            ) -> Vec<(String, <Self as ::glass::objects::Sortable>::DataType)> {
+                let encode_text = <#codec as ::glass::objects::FieldCodec>::encode_text;
+
+                vec![#(#encode),*]
+                    .into_iter()
+                    .map(
+                        |(key, value): (
+                            &str,
+                            Option<<Self as ::glass::objects::Sortable>::DataType>,
+                        )| (key.to_owned(), value),
+                    )
+                    #(#encode_extra)*
+                    .filter_map(|(key, value)| value.map(|x| (key, x)))
+                    .collect()
+            }
+        }
+        }
+    })
+}
+
+/// Returns whether the type is written as `Option<String>`.
+fn is_option_string(ty: &Type) -> bool {
+    let segment = match ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(x) if x.ident == "Option" => x,
+            _ => return false,
+        },
+        _ => return false,
+    };
+
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) => match args.args.first() {
+            Some(GenericArgument::Type(Type::Path(inner))) => inner.path.is_ident("String"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,51 @@
+//! Binary Encodings
+//! A trait over the serde binary backends, so callers can be generic over the encoding.
+use crate::error::GlassError;
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+/// A binary encoding objects can be written in.
+/// Implementors only provide encode and decode, the object functions are built on them.
+pub trait BinaryBackend {
+    /// Encodes any value that implements Serialize.
+    fn encode<S>(value: &S) -> Result<Vec<u8>, GlassError>
+    where
+        S: Serialize;
+
+    /// Decodes bytes into any value that implements Deserialize.
+    fn decode<D>(bytes: &[u8]) -> Result<D, GlassError>
+    where
+        D: DeserializeOwned;
+
+    /// Convert an object that implements Serialize to bytes
+    fn serialize<T>(object: &(Uuid, T)) -> Result<Vec<u8>, GlassError>
+    where
+        T: Serialize,
+    {
+        Self::encode(object)
+    }
+
+    /// Convert objects that implement Serialize to bytes
+    fn serialize_all<T>(objects: &[(Uuid, T)]) -> Result<Vec<u8>, GlassError>
+    where
+        T: Serialize,
+    {
+        Self::encode(&objects)
+    }
+
+    /// Convert bytes into an object that implements Deserialize
+    fn deserialize<T>(bytes: &[u8]) -> Result<(Uuid, T), GlassError>
+    where
+        T: DeserializeOwned,
+    {
+        Self::decode(bytes)
+    }
+
+    /// Convert bytes into objects that implement Deserialize
+    fn deserialize_all<T>(bytes: &[u8]) -> Result<Vec<(Uuid, T)>, GlassError>
+    where
+        T: DeserializeOwned,
+    {
+        Self::decode(bytes)
+    }
+}
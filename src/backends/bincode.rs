@@ -0,0 +1,152 @@
+//! Bincode Functions
+//! These functions can be used to encode an object for the binary transfer protocol.
+//! With the compression feature the encoded bytes can also be gzip compressed for storage.
+use super::binary::BinaryBackend;
+use crate::error::GlassError;
+use crate::objects::FieldCodec;
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+/// The bincode encoding.
+pub struct Bincode;
+
+impl BinaryBackend for Bincode {
+    fn encode<S>(value: &S) -> Result<Vec<u8>, GlassError>
+    where
+        S: Serialize,
+    {
+        Ok(bincode::serialize(value)?)
+    }
+
+    fn decode<D>(bytes: &[u8]) -> Result<D, GlassError>
+    where
+        D: DeserializeOwned,
+    {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+/// Stores every field as bytes, with serialized fields written as bincode.
+/// Text fields are stored as their UTF-8 bytes.
+pub struct BincodeCodec;
+
+impl FieldCodec for BincodeCodec {
+    type DataType = Vec<u8>;
+
+    fn encode_text(value: String) -> Vec<u8> {
+        value.into_bytes()
+    }
+
+    fn decode_text(data: Vec<u8>) -> Option<String> {
+        String::from_utf8(data).ok()
+    }
+
+    fn encode_value<T: Serialize>(value: &T) -> Option<Vec<u8>> {
+        bincode::serialize(value).ok()
+    }
+
+    fn decode_value<T: DeserializeOwned>(data: &Vec<u8>) -> Option<T> {
+        bincode::deserialize(data).ok()
+    }
+}
+
+/// Convert an object that implements Serialize to bytes
+pub fn object_to_bytes<T>(object: &(Uuid, T)) -> Result<Vec<u8>, GlassError>
+where
+    T: Serialize,
+{
+    Bincode::serialize(object)
+}
+
+/// Convert objects that implement Serialize to bytes
+pub fn objects_to_bytes<T>(objects: &[(Uuid, T)]) -> Result<Vec<u8>, GlassError>
+where
+    T: Serialize,
+{
+    Bincode::serialize_all(objects)
+}
+
+/// Convert bytes into an object that implements Deserialize
+pub fn bytes_to_object<T>(bytes: &[u8]) -> Result<(Uuid, T), GlassError>
+where
+    T: DeserializeOwned,
+{
+    Bincode::deserialize(bytes)
+}
+
+/// Convert bytes into objects that implement Deserialize
+pub fn bytes_to_objects<T>(bytes: &[u8]) -> Result<Vec<(Uuid, T)>, GlassError>
+where
+    T: DeserializeOwned,
+{
+    Bincode::deserialize_all(bytes)
+}
+
+/// Returns how many bytes an object will take up once encoded, without encoding it
+pub fn serialized_size<T>(object: &(Uuid, T)) -> Result<u64, GlassError>
+where
+    T: Serialize,
+{
+    Ok(bincode::serialized_size(object)?)
+}
+
+/// Convert an object that implements Serialize to gzip compressed bytes
+/// The level goes from 0 (no compression) to 9 (best compression).
+#[cfg(feature = "compression")]
+pub fn object_to_compressed_bytes<T>(object: &(Uuid, T), level: u32) -> Result<Vec<u8>, GlassError>
+where
+    T: Serialize,
+{
+    compress(&object_to_bytes(object)?, level)
+}
+
+/// Convert objects that implement Serialize to gzip compressed bytes
+/// The level goes from 0 (no compression) to 9 (best compression).
+#[cfg(feature = "compression")]
+pub fn objects_to_compressed_bytes<T>(
+    objects: &[(Uuid, T)],
+    level: u32,
+) -> Result<Vec<u8>, GlassError>
+where
+    T: Serialize,
+{
+    compress(&objects_to_bytes(objects)?, level)
+}
+
+/// Convert gzip compressed bytes into an object that implements Deserialize
+#[cfg(feature = "compression")]
+pub fn compressed_bytes_to_object<T>(bytes: &[u8]) -> Result<(Uuid, T), GlassError>
+where
+    T: DeserializeOwned,
+{
+    bytes_to_object(&decompress(bytes)?)
+}
+
+/// Convert gzip compressed bytes into objects that implement Deserialize
+#[cfg(feature = "compression")]
+pub fn compressed_bytes_to_objects<T>(bytes: &[u8]) -> Result<Vec<(Uuid, T)>, GlassError>
+where
+    T: DeserializeOwned,
+{
+    bytes_to_objects(&decompress(bytes)?)
+}
+
+/// Gzip compresses encoded bytes.
+#[cfg(feature = "compression")]
+fn compress(bytes: &[u8], level: u32) -> Result<Vec<u8>, GlassError> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses gzip compressed bytes.
+#[cfg(feature = "compression")]
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, GlassError> {
+    use std::io::Read;
+
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(bytes).read_to_end(&mut decoded)?;
+    Ok(decoded)
+}
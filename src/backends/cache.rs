@@ -0,0 +1,75 @@
+//! Cache Functions
+//! A write-through cache which serves recently used objects from memory.
+//! Redis remains the source of truth, the cache is only filled from and invalidated by it.
+use crate::backends::redis;
+use crate::error::GlassError;
+use crate::objects::{rainfusion::Mod, Sortable};
+use ::redis::Connection;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use uuid::Uuid;
+
+/// A Redis connection with an LRU cache of recently used mods in front of it.
+pub struct CachedStore {
+    connection: Connection,
+    cache: LruCache<Uuid, Mod>,
+    redis_reads: usize,
+}
+
+impl CachedStore {
+    /// Creates a store holding at most `capacity` mods in memory.
+    pub fn new(connection: Connection, capacity: NonZeroUsize) -> Self {
+        CachedStore {
+            connection,
+            cache: LruCache::new(capacity),
+            redis_reads: 0,
+        }
+    }
+
+    /// Retrieves a mod from the cache, falling back to Redis on a miss.
+    pub fn retrieve(&mut self, uuid: Uuid) -> Result<Mod, GlassError> {
+        if let Some(object) = self.cache.get(&uuid) {
+            return Ok(object.clone());
+        }
+
+        self.redis_reads += 1;
+        let object = Mod::map_to_object(redis::retrieve_object_from_database::<Mod>(
+            &mut self.connection,
+            uuid,
+        )?);
+        self.cache.put(uuid, object.clone());
+
+        Ok(object)
+    }
+
+    /// Inserts a mod into Redis and the cache.
+    /// Returns the UUID of where the object is on the database.
+    pub fn insert(&mut self, object: Mod, uuid: Option<Uuid>) -> Result<Uuid, GlassError> {
+        let uuid = redis::insert_object_into_database(&mut self.connection, object.clone(), uuid)?;
+        self.cache.put(uuid, object);
+
+        Ok(uuid)
+    }
+
+    /// Edits fields of a mod in Redis and drops it from the cache.
+    pub fn edit(&mut self, changes: Vec<(String, String)>, uuid: Uuid) -> Result<(), GlassError> {
+        self.cache.pop(&uuid);
+        redis::edit_object_from_database::<Mod>(&mut self.connection, changes, uuid)
+    }
+
+    /// Removes a mod from Redis and the cache.
+    pub fn remove(&mut self, uuid: Uuid) -> Result<(), GlassError> {
+        self.cache.pop(&uuid);
+        redis::remove_object_from_database::<Mod>(&mut self.connection, uuid)
+    }
+
+    /// The number of times a retrieve had to read from Redis.
+    pub fn redis_reads(&self) -> usize {
+        self.redis_reads
+    }
+
+    /// The underlying Redis connection.
+    pub fn connection(&mut self) -> &mut Connection {
+        &mut self.connection
+    }
+}
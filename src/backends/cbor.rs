@@ -0,0 +1,58 @@
+//! CBOR Functions
+//! These functions can be used to encode an object for clients that speak CBOR.
+//! Objects are written as maps keyed by field name, with missing values written as null.
+use super::binary::BinaryBackend;
+use crate::error::GlassError;
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+/// The CBOR encoding.
+pub struct Cbor;
+
+impl BinaryBackend for Cbor {
+    fn encode<S>(value: &S) -> Result<Vec<u8>, GlassError>
+    where
+        S: Serialize,
+    {
+        Ok(serde_cbor::to_vec(value)?)
+    }
+
+    fn decode<D>(bytes: &[u8]) -> Result<D, GlassError>
+    where
+        D: DeserializeOwned,
+    {
+        Ok(serde_cbor::from_slice(bytes)?)
+    }
+}
+
+/// Convert an object that implements Serialize to bytes
+pub fn object_to_bytes<T>(object: &(Uuid, T)) -> Result<Vec<u8>, GlassError>
+where
+    T: Serialize,
+{
+    Cbor::serialize(object)
+}
+
+/// Convert objects that implement Serialize to bytes
+pub fn objects_to_bytes<T>(objects: &[(Uuid, T)]) -> Result<Vec<u8>, GlassError>
+where
+    T: Serialize,
+{
+    Cbor::serialize_all(objects)
+}
+
+/// Convert bytes into an object that implements Deserialize
+pub fn bytes_to_object<T>(bytes: &[u8]) -> Result<(Uuid, T), GlassError>
+where
+    T: DeserializeOwned,
+{
+    Cbor::deserialize(bytes)
+}
+
+/// Convert bytes into objects that implement Deserialize
+pub fn bytes_to_objects<T>(bytes: &[u8]) -> Result<Vec<(Uuid, T)>, GlassError>
+where
+    T: DeserializeOwned,
+{
+    Cbor::deserialize_all(bytes)
+}
@@ -0,0 +1,197 @@
+//! CSV Functions
+//! These functions can be used to move objects between spreadsheets and the other backends.
+#[cfg(feature = "redis_backend")]
+use crate::backends::redis;
+use crate::error::GlassError;
+use crate::objects::rainfusion::{Mod, ModType};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::io::{Read, Write};
+use uuid::Uuid;
+
+/// Number of rows inserted into Redis per pipeline while importing.
+pub const IMPORT_BATCH_SIZE: usize = 100;
+
+/// A single row of a mod spreadsheet.
+/// Tags are joined together with semicolons and the optional extra column holds a JSON object.
+#[derive(Deserialize, Debug)]
+struct ModRow {
+    name: Option<String>,
+    author: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    version: Option<String>,
+    item_type: Option<String>,
+    tags: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_extra_cell")]
+    extra: Option<Map<String, Value>>,
+}
+
+impl From<ModRow> for Mod {
+    fn from(row: ModRow) -> Self {
+        Mod {
+            name: row.name,
+            author: row.author,
+            summary: row.summary,
+            description: row.description,
+            version: row.version,
+            item_type: ModType::from(row.item_type.unwrap_or_default()),
+            dependencies: None,
+            tags: row.tags.map(|x| split_tags(&x)),
+            extra: row.extra,
+        }
+    }
+}
+
+/// A single row of an exported mod spreadsheet.
+/// Dependencies are flattened to how many there are, tags are joined together with semicolons
+/// and extra attributes are written as a JSON object.
+#[derive(Serialize, Deserialize, Debug)]
+struct ExportRow {
+    uuid: Uuid,
+    name: Option<String>,
+    author: Option<String>,
+    summary: Option<String>,
+    description: Option<String>,
+    version: Option<String>,
+    item_type: String,
+    tags: Option<String>,
+    dependencies: usize,
+    #[serde(
+        default,
+        serialize_with = "serialize_extra_cell",
+        deserialize_with = "deserialize_extra_cell"
+    )]
+    extra: Option<Map<String, Value>>,
+}
+
+/// Writes extra attributes as a JSON object in one cell.
+fn serialize_extra_cell<S>(
+    extra: &Option<Map<String, Value>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    extra
+        .as_ref()
+        .map(|x| Value::Object(x.clone()).to_string())
+        .serialize(serializer)
+}
+
+/// Reads extra attributes from a JSON object cell, where an empty cell has none.
+fn deserialize_extra_cell<'de, D>(de: D) -> Result<Option<Map<String, Value>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(de)?
+        .filter(|x| !x.is_empty())
+        .map(|x| serde_json::from_str(&x).map_err(de::Error::custom))
+        .transpose()
+}
+
+/// Splits a semicolon joined cell into tags.
+fn split_tags(cell: &str) -> Vec<String> {
+    cell.split(';').map(|x| x.trim().to_owned()).collect()
+}
+
+/// Function to write mods as CSV with a header row, one row per mod.
+/// CSV can't hold nested fields, so dependencies are written as a count, tags are joined
+/// with semicolons and extra attributes are written as a JSON object. Reading the file back
+/// with objects_from_csv keeps the scalar fields, tags and extra attributes, but the
+/// dependencies are lost and empty strings read back as None.
+pub fn objects_to_csv<W: Write>(writer: W, objects: &[(Uuid, Mod)]) -> Result<(), GlassError> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    for (uuid, object) in objects {
+        writer.serialize(ExportRow {
+            uuid: *uuid,
+            name: object.name.clone(),
+            author: object.author.clone(),
+            summary: object.summary.clone(),
+            description: object.description.clone(),
+            version: object.version.clone(),
+            item_type: object.item_type.to_string(),
+            tags: object.tags.as_ref().map(|x| x.join(";")),
+            dependencies: object.dependencies.as_ref().map_or(0, Vec::len),
+            extra: object.extra.clone(),
+        })?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Function to read mods written by objects_to_csv.
+/// The dependency count can't be turned back into dependencies, so they are always None.
+pub fn objects_from_csv<R: Read>(reader: R) -> Result<Vec<(Uuid, Mod)>, GlassError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut objects = Vec::new();
+
+    for row in reader.deserialize::<ExportRow>() {
+        let row = row?;
+        objects.push((
+            row.uuid,
+            Mod {
+                name: row.name,
+                author: row.author,
+                summary: row.summary,
+                description: row.description,
+                version: row.version,
+                item_type: row.item_type.parse()?,
+                dependencies: None,
+                tags: row.tags.map(|x| split_tags(&x)),
+                extra: row.extra,
+            },
+        ));
+    }
+
+    Ok(objects)
+}
+
+/// The outcome of a CSV import.
+#[derive(Debug, Default)]
+pub struct CsvImport {
+    /// The UUIDs of the inserted mods, in file order.
+    pub inserted: Vec<Uuid>,
+    /// The rows which could not be parsed, by line number.
+    pub errors: Vec<(u64, GlassError)>,
+}
+
+/// Function to import mods from a CSV stream into a local Redis database.
+/// Rows are read one at a time and inserted in batches, so the file is never fully in memory.
+/// Rows which fail to parse are reported in the result rather than aborting the import.
+#[cfg(feature = "redis_backend")]
+pub fn import_csv_stream<R: Read>(
+    connection: &mut ::redis::Connection,
+    reader: R,
+) -> Result<CsvImport, GlassError> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut result = CsvImport::default();
+    let mut batch: Vec<(Option<Uuid>, Mod)> = Vec::with_capacity(IMPORT_BATCH_SIZE);
+
+    for row in reader.deserialize::<ModRow>() {
+        match row {
+            Ok(row) => batch.push((None, Mod::from(row))),
+            Err(error) => {
+                let line = error.position().map_or(0, |x| x.line());
+                result.errors.push((line, error.into()));
+            }
+        }
+
+        if batch.len() == IMPORT_BATCH_SIZE {
+            let objects = std::mem::replace(&mut batch, Vec::with_capacity(IMPORT_BATCH_SIZE));
+            result
+                .inserted
+                .extend(redis::insert_objects_into_database(connection, objects)?);
+        }
+    }
+
+    if !batch.is_empty() {
+        result
+            .inserted
+            .extend(redis::insert_objects_into_database(connection, batch)?);
+    }
+
+    Ok(result)
+}
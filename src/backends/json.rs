@@ -1,36 +1,211 @@
 //! JSON Functions
 //! These functions can be used to allow an object to perform JSON actions.
-use std::error::Error;
+use crate::error::{report, GlassError};
+use crate::objects::Sortable;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserializer, Serializer};
+use serde_json::Value;
+use std::fmt;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
 use uuid::Uuid;
 
 /// Convert an object that implements Serialize to a String
-pub fn object_to_string<T>(object: (Uuid, T)) -> Result<String, Box<dyn Error>>
+pub fn object_to_string<T>(object: (Uuid, T)) -> Result<String, GlassError>
 where
     T: serde::ser::Serialize,
 {
-    Ok(serde_json::to_string(&object)?)
+    serde_json::to_string(&object).map_err(|e| report(GlassError::Serialization(Box::new(e))))
 }
 
 /// Convert objects that implement Serialize to a String
-pub fn objects_to_string<T>(objects: &[(Uuid, T)]) -> Result<String, Box<dyn Error>>
+pub fn objects_to_string<T>(objects: &[(Uuid, T)]) -> Result<String, GlassError>
 where
     T: serde::ser::Serialize,
 {
-    Ok(serde_json::to_string(&objects)?)
+    serde_json::to_string(&objects).map_err(|e| report(GlassError::Serialization(Box::new(e))))
 }
 
 /// Convert a JSON string into an object that implement Deserialize
-pub fn string_to_object<'de, T>(string: &'de str) -> Result<(Uuid, T), Box<dyn Error>>
+pub fn string_to_object<'de, T>(string: &'de str) -> Result<(Uuid, T), GlassError>
 where
     T: serde::de::Deserialize<'de>,
 {
-    Ok(serde_json::from_str(string)?)
+    serde_json::from_str(string).map_err(|e| report(GlassError::Deserialization(Box::new(e))))
 }
 
 /// Convert a JSON string into objects that implement Deserialize
-pub fn string_to_objects<'de, T>(string: &'de str) -> Result<Vec<(Uuid, T)>, Box<dyn Error>>
+pub fn string_to_objects<'de, T>(string: &'de str) -> Result<Vec<(Uuid, T)>, GlassError>
 where
     T: serde::de::Deserialize<'de>,
 {
-    Ok(serde_json::from_str(string)?)
+    serde_json::from_str(string).map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Read objects one at a time from a reader of a JSON array, as written by objects_to_string
+/// and objects_to_writer. Each object is handed to `f` as soon as it is read, so only the object
+/// being read is held in memory. Wrap files in a BufReader.
+/// Stops at the first error returned by `f`, returning it. Returns how many objects were read.
+pub fn objects_from_reader<R, T, F>(reader: R, f: F) -> Result<usize, GlassError>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+    F: FnMut((Uuid, T)) -> Result<(), GlassError>,
+{
+    let mut error = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = deserializer
+        .deserialize_seq(ArrayVisitor {
+            f,
+            error: &mut error,
+            marker: PhantomData,
+        })
+        .and_then(|count| deserializer.end().map(|_| count));
+
+    match (result, error) {
+        (_, Some(e)) => Err(e),
+        (Ok(count), None) => Ok(count),
+        (Err(e), None) => Err(report(GlassError::Deserialization(Box::new(e)))),
+    }
+}
+
+/// Hands each object of a JSON array to a function as soon as it is read.
+struct ArrayVisitor<'a, T, F> {
+    f: F,
+    error: &'a mut Option<GlassError>,
+    marker: PhantomData<T>,
+}
+
+impl<'de, T, F> Visitor<'de> for ArrayVisitor<'_, T, F>
+where
+    T: serde::de::DeserializeOwned,
+    F: FnMut((Uuid, T)) -> Result<(), GlassError>,
+{
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of objects")
+    }
+
+    fn visit_seq<A>(mut self, mut seq: A) -> Result<usize, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut count = 0;
+        while let Some(object) = seq.next_element()? {
+            if let Err(e) = (self.f)(object) {
+                *self.error = Some(e);
+                return Err(de::Error::custom("stopped by the object handler"));
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Write objects one at a time to a writer as a JSON array.
+/// The array can be read back with objects_from_reader, so wrap files in a BufWriter.
+pub fn objects_to_writer<W, I, T>(mut writer: W, objects: I) -> Result<(), GlassError>
+where
+    W: Write,
+    I: IntoIterator<Item = (Uuid, T)>,
+    T: serde::ser::Serialize,
+{
+    serde_json::Serializer::new(&mut writer)
+        .collect_seq(objects)
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+
+    Ok(writer.flush()?)
+}
+
+/// Read objects one at a time from a reader of JSON lines, as written by objects_to_lines_writer.
+/// Only the object being read is held in memory, so wrap files in a BufReader.
+pub fn objects_from_lines_reader<R, T>(
+    reader: R,
+) -> impl Iterator<Item = Result<(Uuid, T), GlassError>>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    serde_json::Deserializer::from_reader(reader)
+        .into_iter()
+        .map(|x| x.map_err(|e| report(GlassError::Deserialization(Box::new(e)))))
+}
+
+/// Write objects one at a time to a writer, one JSON line per object.
+/// The lines can be read back with objects_from_lines_reader, so wrap files in a BufWriter.
+pub fn objects_to_lines_writer<W, I, T>(mut writer: W, objects: I) -> Result<(), GlassError>
+where
+    W: Write,
+    I: IntoIterator<Item = (Uuid, T)>,
+    T: serde::ser::Serialize,
+{
+    for object in objects {
+        serde_json::to_writer(&mut writer, &object)
+            .map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+/// Convert an object that implements Serialize to a String, keeping only its public fields.
+pub fn object_to_public_string<O>(object: &O) -> Result<String, GlassError>
+where
+    O: Sortable + serde::ser::Serialize,
+{
+    let mut value =
+        serde_json::to_value(object).map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+
+    if let (Value::Object(ref mut map), Some(public)) = (&mut value, O::public_fields()) {
+        map.retain(|key, _| public.contains(&key.as_str()));
+    }
+
+    serde_json::to_string(&value).map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert an object that implements Serialize to a String, leaving out default values.
+/// Fields equal to the default of the object, null fields and empty collections are omitted.
+pub fn serialize_sparse<T>(object: &T) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize + Default,
+{
+    let default = serde_json::to_value(T::default())
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+    let mut value =
+        serde_json::to_value(object).map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+
+    if let Value::Object(ref mut map) = value {
+        map.retain(|key, field| {
+            let empty = match field {
+                Value::Null => true,
+                Value::Array(x) => x.is_empty(),
+                Value::Object(x) => x.is_empty(),
+                _ => false,
+            };
+
+            !empty && default.get(key) != Some(field)
+        });
+    }
+
+    serde_json::to_string(&value).map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert a String written by serialize_sparse into an object, filling in the left out defaults.
+pub fn deserialize_sparse<T>(string: &str) -> Result<T, GlassError>
+where
+    T: serde::ser::Serialize + serde::de::DeserializeOwned + Default,
+{
+    let mut value = serde_json::to_value(T::default())
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+    let sparse: Value = serde_json::from_str(string)
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?;
+
+    match (&mut value, sparse) {
+        (Value::Object(map), Value::Object(sparse)) => map.extend(sparse),
+        (_, sparse) => value = sparse,
+    }
+
+    serde_json::from_value(value).map_err(|e| report(GlassError::Deserialization(Box::new(e))))
 }
@@ -0,0 +1,240 @@
+//! In-Memory Functions
+//! These functions mirror the Redis functions using a store held in the process.
+//! Each function takes a MemoryStore where the Redis functions take a connection, so code and
+//! tests can run without a Redis database. Nothing is persisted once the store is dropped.
+
+use crate::error::{report, GlassError};
+use crate::objects::Sortable;
+use redis::{FromRedisValue, ToRedisArgs, Value};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use uuid::Uuid;
+
+/// Custom Type Definitions
+type FieldMap<T> = HashMap<String, T>;
+type MemoryResult<T> = Result<Vec<(Uuid, FieldMap<T>)>, GlassError>;
+
+/// An in-memory store of object indexes, safe to share between threads.
+#[derive(Default)]
+pub struct MemoryStore {
+    indexes: Mutex<HashMap<String, MemoryIndex>>,
+}
+
+/// The objects of one index, with their fields held as the bytes Redis would store.
+#[derive(Default)]
+struct MemoryIndex {
+    scores: HashMap<Uuid, i64>,
+    counter: i64,
+    objects: HashMap<Uuid, FieldMap<Vec<u8>>>,
+}
+
+impl MemoryIndex {
+    /// Returns the UUIDs in the index ordered like a Redis sorted set, by score then key.
+    fn ordered(&self) -> Vec<Uuid> {
+        let mut uuids: Vec<(i64, String, Uuid)> = self
+            .scores
+            .iter()
+            .map(|(uuid, score)| (*score, uuid.to_simple().to_string(), *uuid))
+            .collect();
+        uuids.sort();
+        uuids.into_iter().map(|x| x.2).collect()
+    }
+
+    /// Decodes the fields of an object, which must be in the index.
+    fn fields<T: FromRedisValue>(&self, uuid: Uuid) -> Result<FieldMap<T>, GlassError> {
+        let fields = match (self.scores.contains_key(&uuid), self.objects.get(&uuid)) {
+            (true, Some(fields)) => fields,
+            _ => return Err(report(GlassError::NotIndexed(uuid))),
+        };
+
+        fields
+            .iter()
+            .map(|(key, value)| {
+                Ok((
+                    key.clone(),
+                    T::from_redis_value(&Value::Data(value.clone()))
+                        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl MemoryStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Locks the indexes, carrying on with the data if another thread panicked holding the lock.
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, MemoryIndex>> {
+        self.indexes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Converts a field value into the bytes Redis would hold for it.
+fn field_bytes<T: ToRedisArgs>(value: &T) -> Vec<u8> {
+    value.to_redis_args().concat()
+}
+
+/// Function to insert an object into a MemoryStore.
+/// Returns the UUID of where the object is in the store.
+pub fn insert_object_into_database<O>(
+    store: &MemoryStore,
+    object: O,
+    uuid: Option<Uuid>,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable,
+{
+    insert_objects_into_database(store, vec![(uuid, object)]).map(|x| x[0])
+}
+
+/// Function to insert many objects into a MemoryStore.
+/// Returns the UUIDs of where the objects are in the store, in insertion order.
+pub fn insert_objects_into_database<O>(
+    store: &MemoryStore,
+    objects: Vec<(Option<Uuid>, O)>,
+) -> Result<Vec<Uuid>, GlassError>
+where
+    O: Sortable,
+{
+    // Objects carrying their own UUID are stored under it.
+    let objects: Vec<(Option<Uuid>, O)> = objects
+        .into_iter()
+        .map(|(uuid, object)| (object.primary_key().or(uuid), object))
+        .collect();
+
+    // The nil UUID is used as the empty index sentinel, so it can't be a key.
+    if let Some(uuid) = objects.iter().find_map(|x| x.0.filter(Uuid::is_nil)) {
+        return Err(report(GlassError::InvalidKey(uuid)));
+    }
+
+    let mut indexes = store.lock();
+    let index = indexes.entry(O::object_to_index().to_owned()).or_default();
+
+    Ok(objects
+        .into_iter()
+        .map(|(uuid, object)| {
+            let uuid = uuid.unwrap_or_else(Uuid::new_v4);
+
+            // Objects already in the index keep their score, like ZADD NX.
+            if !index.scores.contains_key(&uuid) {
+                index.counter += 1;
+                index.scores.insert(uuid, index.counter);
+            }
+
+            // The new fields replace the old ones, so fields the object no longer has are gone.
+            let fields = object
+                .object_to_map()
+                .into_iter()
+                .map(|(key, value)| (key, field_bytes(&value)))
+                .collect();
+            index.objects.insert(uuid, fields);
+
+            uuid
+        })
+        .collect())
+}
+
+/// Function to remove an object from a MemoryStore.
+pub fn remove_object_from_database<O>(store: &MemoryStore, uuid: Uuid) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    let mut indexes = store.lock();
+    let index = indexes.get_mut(O::object_to_index());
+
+    match index.and_then(|x| x.scores.remove(&uuid).map(|_| x)) {
+        Some(index) => {
+            index.objects.remove(&uuid);
+            Ok(())
+        }
+        None => Err(report(GlassError::NotIndexed(uuid))),
+    }
+}
+
+/// Function to check if an object is in the index of a MemoryStore.
+pub fn object_exists<O>(store: &MemoryStore, uuid: Uuid) -> Result<bool, GlassError>
+where
+    O: Sortable,
+{
+    Ok(store
+        .lock()
+        .get(O::object_to_index())
+        .is_some_and(|x| x.scores.contains_key(&uuid)))
+}
+
+/// Function to retrieve a object in a MemoryStore.
+pub fn retrieve_object_from_database<O>(
+    store: &MemoryStore,
+    uuid: Uuid,
+) -> Result<FieldMap<O::DataType>, GlassError>
+where
+    O: Sortable,
+{
+    match store.lock().get(O::object_to_index()) {
+        Some(index) => index.fields(uuid),
+        None => Err(report(GlassError::NotIndexed(uuid))),
+    }
+}
+
+/// Function to retrieve an object in a MemoryStore as the object itself.
+pub fn get_object<O>(store: &MemoryStore, uuid: Uuid) -> Result<O, GlassError>
+where
+    O: Sortable,
+{
+    retrieve_object_from_database::<O>(store, uuid).map(O::map_to_object)
+}
+
+/// Function to request a page of objects from a MemoryStore.
+/// Pages start at 1 and hold `page_size` objects each, so the last page may hold fewer.
+/// A page below 1 or a page size below 1 holds no objects.
+pub fn request_group_of_objects<O>(
+    store: &MemoryStore,
+    page: isize,
+    page_size: isize,
+) -> MemoryResult<O::DataType>
+where
+    O: Sortable,
+{
+    if page < 1 || page_size < 1 {
+        return Ok(Vec::new());
+    }
+
+    request_range::<O>(store, (page_size * (page - 1)) as usize, page_size as usize)
+}
+
+/// Function to request all the objects from a MemoryStore.
+/// Returns the objects from the store with the key and object in a Vec.
+pub fn request_all_objects<O>(store: &MemoryStore) -> MemoryResult<O::DataType>
+where
+    O: Sortable,
+{
+    request_range::<O>(store, 0, usize::MAX)
+}
+
+/// Returns `take` objects of an index in score order, starting after `skip` objects.
+fn request_range<O>(store: &MemoryStore, skip: usize, take: usize) -> MemoryResult<O::DataType>
+where
+    O: Sortable,
+{
+    let indexes = store.lock();
+    let index = match indexes.get(O::object_to_index()) {
+        Some(x) => x,
+        None => return Ok(Vec::new()),
+    };
+
+    index
+        .ordered()
+        .into_iter()
+        .skip(skip)
+        .take(take)
+        .map(|uuid| Ok((uuid, index.fields(uuid)?)))
+        .collect()
+}
+
+/// Function to return the current object count in a index of a MemoryStore.
+pub fn current_object_count(store: &MemoryStore, index: &str) -> Result<i64, GlassError> {
+    Ok(store.lock().get(index).map_or(0, |x| x.scores.len() as i64))
+}
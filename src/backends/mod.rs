@@ -1,8 +1,148 @@
 //! The backends for the objects.
 //! Each backend can be disabled / enabled depending on the users configuration.
+#[cfg(any(feature = "bincode_backend", feature = "cbor_backend"))]
+pub mod binary;
+#[cfg(feature = "bincode_backend")]
+pub mod bincode;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "cbor_backend")]
+pub mod cbor;
+#[cfg(feature = "csv_backend")]
+pub mod csv;
 #[cfg(feature = "json_backend")]
 pub mod json;
+#[cfg(feature = "memory_backend")]
+pub mod memory;
+#[cfg(feature = "json_patch")]
+pub mod patch;
+#[cfg(feature = "protobuf_backend")]
+pub mod protobuf;
 #[cfg(feature = "redis_backend")]
 pub mod redis;
+#[cfg(feature = "redis_async")]
+pub mod redis_async;
+#[cfg(feature = "ron_backend")]
+pub mod ron;
+#[cfg(feature = "search")]
+pub mod search;
+#[cfg(feature = "toml_backend")]
+pub mod toml;
+#[cfg(feature = "xml_backend")]
+pub mod xml;
 #[cfg(feature = "yaml_backend")]
 pub mod yaml;
+
+/// Returns the names of the backends compiled into this build.
+pub fn enabled_backends() -> Vec<&'static str> {
+    let mut backends = Vec::new();
+
+    if cfg!(feature = "bincode_backend") {
+        backends.push("bincode");
+    }
+    if cfg!(feature = "cache") {
+        backends.push("cache");
+    }
+    if cfg!(feature = "cbor_backend") {
+        backends.push("cbor");
+    }
+    if cfg!(feature = "csv_backend") {
+        backends.push("csv");
+    }
+    if cfg!(feature = "json_backend") {
+        backends.push("json");
+    }
+    if cfg!(feature = "json_patch") {
+        backends.push("patch");
+    }
+    if cfg!(feature = "memory_backend") {
+        backends.push("memory");
+    }
+    if cfg!(feature = "protobuf_backend") {
+        backends.push("protobuf");
+    }
+    if cfg!(feature = "redis_backend") {
+        backends.push("redis");
+    }
+    if cfg!(feature = "redis_async") {
+        backends.push("redis_async");
+    }
+    if cfg!(feature = "ron_backend") {
+        backends.push("ron");
+    }
+    if cfg!(feature = "search") {
+        backends.push("search");
+    }
+    if cfg!(feature = "toml_backend") {
+        backends.push("toml");
+    }
+    if cfg!(feature = "xml_backend") {
+        backends.push("xml");
+    }
+    if cfg!(feature = "yaml_backend") {
+        backends.push("yaml");
+    }
+
+    backends
+}
+
+#[cfg(test)]
+mod tests {
+    use super::enabled_backends;
+
+    #[test]
+    fn test_enabled_backends() {
+        let backends = enabled_backends();
+
+        assert_eq!(
+            backends.contains(&"bincode"),
+            cfg!(feature = "bincode_backend")
+        );
+        assert_eq!(backends.contains(&"cache"), cfg!(feature = "cache"));
+        assert_eq!(backends.contains(&"cbor"), cfg!(feature = "cbor_backend"));
+        assert_eq!(backends.contains(&"csv"), cfg!(feature = "csv_backend"));
+        assert_eq!(backends.contains(&"json"), cfg!(feature = "json_backend"));
+        assert_eq!(backends.contains(&"patch"), cfg!(feature = "json_patch"));
+        assert_eq!(
+            backends.contains(&"memory"),
+            cfg!(feature = "memory_backend")
+        );
+        assert_eq!(
+            backends.contains(&"protobuf"),
+            cfg!(feature = "protobuf_backend")
+        );
+        assert_eq!(backends.contains(&"redis"), cfg!(feature = "redis_backend"));
+        assert_eq!(
+            backends.contains(&"redis_async"),
+            cfg!(feature = "redis_async")
+        );
+        assert_eq!(backends.contains(&"ron"), cfg!(feature = "ron_backend"));
+        assert_eq!(backends.contains(&"search"), cfg!(feature = "search"));
+        assert_eq!(backends.contains(&"toml"), cfg!(feature = "toml_backend"));
+        assert_eq!(backends.contains(&"xml"), cfg!(feature = "xml_backend"));
+        assert_eq!(backends.contains(&"yaml"), cfg!(feature = "yaml_backend"));
+        assert_eq!(
+            backends.len(),
+            [
+                cfg!(feature = "bincode_backend"),
+                cfg!(feature = "cache"),
+                cfg!(feature = "cbor_backend"),
+                cfg!(feature = "csv_backend"),
+                cfg!(feature = "json_backend"),
+                cfg!(feature = "json_patch"),
+                cfg!(feature = "memory_backend"),
+                cfg!(feature = "protobuf_backend"),
+                cfg!(feature = "redis_backend"),
+                cfg!(feature = "redis_async"),
+                cfg!(feature = "ron_backend"),
+                cfg!(feature = "search"),
+                cfg!(feature = "toml_backend"),
+                cfg!(feature = "xml_backend"),
+                cfg!(feature = "yaml_backend"),
+            ]
+            .iter()
+            .filter(|x| **x)
+            .count()
+        );
+    }
+}
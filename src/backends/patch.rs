@@ -0,0 +1,44 @@
+//! JSON Patch Functions
+//! These functions apply RFC 6902 JSON Patch documents to objects stored in Redis.
+//! The patch is applied to the JSON form of the object and only the changed fields are written.
+use crate::backends::redis;
+use crate::error::{report, GlassError};
+use crate::objects::{rainfusion::Mod, Sortable};
+use ::redis::Connection;
+use json_patch::Patch;
+use uuid::Uuid;
+
+/// Function to apply a JSON patch to a mod in a local Redis database.
+/// Returns the patched mod, nothing is written if the patched document isn't a valid mod.
+pub fn apply_json_patch(
+    connection: &mut Connection,
+    uuid: Uuid,
+    patch: &serde_json::Value,
+) -> Result<Mod, GlassError> {
+    let patch: Patch = serde_json::from_value(patch.clone())
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?;
+    let object = Mod::map_to_object(redis::retrieve_object_from_database::<Mod>(
+        connection, uuid,
+    )?);
+
+    // Apply the patch to the JSON form and make sure it is still a mod.
+    let mut document = serde_json::to_value(&object)
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+    json_patch::patch(&mut document, &patch)?;
+    let patched: Mod = serde_json::from_value(document)
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?;
+
+    // Only write the fields the patch changed.
+    let before = object.object_to_map();
+    let changes: Vec<(String, String)> = patched
+        .object_to_map()
+        .into_iter()
+        .filter(|item| !before.contains(item))
+        .collect();
+
+    if !changes.is_empty() {
+        redis::edit_object_from_database::<Mod>(connection, changes, uuid)?;
+    }
+
+    Ok(patched)
+}
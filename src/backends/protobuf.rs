@@ -0,0 +1,165 @@
+//! Protobuf Functions
+//! These functions can be used to encode a Mod for services that speak Protobuf.
+//! The messages mirror the schema in `proto/glass.proto`, with UUIDs stored as 16 raw bytes.
+use crate::error::{report, GlassError};
+use crate::objects::rainfusion::{Mod, ModDependency, ModType};
+use prost::Message;
+use std::convert::TryFrom;
+use uuid::Uuid;
+
+/// Protobuf messages matching `proto/glass.proto`.
+mod proto {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ModType {
+        Mod = 0,
+        Library = 1,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct ModDependency {
+        #[prost(bytes = "vec", tag = "1")]
+        pub uuid: Vec<u8>,
+        #[prost(string, tag = "2")]
+        pub version: String,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Dependencies {
+        #[prost(message, repeated, tag = "1")]
+        pub dependencies: Vec<ModDependency>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Tags {
+        #[prost(string, repeated, tag = "1")]
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Mod {
+        #[prost(string, optional, tag = "1")]
+        pub name: Option<String>,
+        #[prost(string, optional, tag = "2")]
+        pub author: Option<String>,
+        #[prost(string, optional, tag = "3")]
+        pub summary: Option<String>,
+        #[prost(string, optional, tag = "4")]
+        pub description: Option<String>,
+        #[prost(string, optional, tag = "5")]
+        pub version: Option<String>,
+        #[prost(enumeration = "ModType", tag = "6")]
+        pub item_type: i32,
+        #[prost(message, optional, tag = "7")]
+        pub dependencies: Option<Dependencies>,
+        #[prost(message, optional, tag = "8")]
+        pub tags: Option<Tags>,
+        #[prost(map = "string, string", tag = "9")]
+        pub extra: std::collections::HashMap<String, String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Object {
+        #[prost(bytes = "vec", tag = "1")]
+        pub uuid: Vec<u8>,
+        #[prost(message, optional, tag = "2")]
+        pub object: Option<Mod>,
+    }
+}
+
+/// Convert a Mod to bytes
+pub fn object_to_bytes(object: &(Uuid, Mod)) -> Result<Vec<u8>, GlassError> {
+    let (uuid, object) = object;
+
+    let message = proto::Object {
+        uuid: uuid.as_bytes().to_vec(),
+        object: Some(proto::Mod {
+            name: object.name.clone(),
+            author: object.author.clone(),
+            summary: object.summary.clone(),
+            description: object.description.clone(),
+            version: object.version.clone(),
+            item_type: match object.item_type {
+                ModType::Mod => proto::ModType::Mod,
+                ModType::Library => proto::ModType::Library,
+            } as i32,
+            dependencies: object.dependencies.as_ref().map(|x| proto::Dependencies {
+                dependencies: x
+                    .iter()
+                    .map(|(uuid, dependency)| proto::ModDependency {
+                        uuid: uuid.as_bytes().to_vec(),
+                        version: dependency.version.clone(),
+                    })
+                    .collect(),
+            }),
+            tags: object.tags.clone().map(|tags| proto::Tags { tags }),
+            extra: object
+                .extra
+                .iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), value.to_string()))
+                .collect(),
+        }),
+    };
+
+    Ok(message.encode_to_vec())
+}
+
+/// Convert bytes into a Mod
+/// Like the other backends, values this version doesn't know read as their defaults,
+/// so a missing object reads as Mod::default() and an unknown item type as ModType::Mod.
+/// Only bytes that aren't a valid message or UUID are an error.
+pub fn bytes_to_object(bytes: &[u8]) -> Result<(Uuid, Mod), GlassError> {
+    let message = proto::Object::decode(bytes)?;
+    let object = message.object.unwrap_or_default();
+
+    let item_type = match proto::ModType::try_from(object.item_type) {
+        Ok(proto::ModType::Mod) => ModType::Mod,
+        Ok(proto::ModType::Library) => ModType::Library,
+        Err(_) => ModType::default(),
+    };
+
+    let dependencies = match object.dependencies {
+        Some(x) => Some(
+            x.dependencies
+                .into_iter()
+                .map(|x| {
+                    Ok((
+                        Uuid::from_slice(&x.uuid)?,
+                        ModDependency { version: x.version },
+                    ))
+                })
+                .collect::<Result<Vec<_>, GlassError>>()?,
+        ),
+        None => None,
+    };
+
+    // Maps can't be told apart from empty ones, so no attributes reads back as None.
+    let extra = if object.extra.is_empty() {
+        None
+    } else {
+        Some(
+            object
+                .extra
+                .into_iter()
+                .map(|(key, value)| Ok((key, serde_json::from_str(&value)?)))
+                .collect::<Result<_, serde_json::Error>>()
+                .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?,
+        )
+    };
+
+    Ok((
+        Uuid::from_slice(&message.uuid)?,
+        Mod {
+            name: object.name,
+            author: object.author,
+            summary: object.summary,
+            description: object.description,
+            version: object.version,
+            item_type,
+            dependencies,
+            tags: object.tags.map(|x| x.tags),
+            extra,
+        },
+    ))
+}
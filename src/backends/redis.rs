@@ -2,15 +2,58 @@
 //! These functions can be used to allow an object to perform Redis database actions.
 //! The configuration can be used to generate a connection to the database.
 
-use crate::objects::Sortable;
-use redis::{Client, Commands, Connection, ConnectionAddr, ConnectionInfo};
+use crate::error::{report, unreported, GlassError};
+use crate::objects::{
+    rainfusion::{topo_sort, Mod, ModType},
+    Sortable, EXTRA_PREFIX,
+};
+use redis::{
+    Client, Commands, Connection, ConnectionAddr, ConnectionInfo, ErrorKind, IntoConnectionInfo,
+    PipelineCommands, RedisError,
+};
 use serde::Deserialize;
-use std::{collections::HashMap, error::Error, path::PathBuf, str::FromStr};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use uuid::Uuid;
 
 /// Custom Type Definitions
 type FieldMap<T> = HashMap<String, T>;
-type RedisResult<T> = Result<Vec<(Uuid, FieldMap<T>)>, Box<dyn Error>>;
+type RedisResult<T> = Result<Vec<(Uuid, FieldMap<T>)>, GlassError>;
+type ScanResult<T> = Result<(Vec<(Uuid, FieldMap<T>)>, Option<ScanCursor>), GlassError>;
+
+/// The position of a page in an index: the score and UUID of the last object read.
+pub type ScanCursor = (i64, Uuid);
+type LenientResult<O> = Result<(Vec<(Uuid, O)>, Vec<(Uuid, GlassError)>), GlassError>;
+
+/// The default maximum number of commands sent in one pipeline by batch functions.
+pub const DEFAULT_MAX_PIPELINE_COMMANDS: usize = 1000;
+
+/// Field flagging an object as soft deleted.
+const DELETED_FIELD: &str = "deleted";
+
+/// Enters a tracing span for a Redis operation that lasts until the end of the enclosing block.
+/// Compiles out without the otel feature, so the fields are never evaluated.
+macro_rules! operation_span {
+    ($operation:literal, $index:expr) => {
+        #[cfg(feature = "otel")]
+        let _span =
+            tracing::info_span!("glass.redis", operation = $operation, index = %$index).entered();
+    };
+    ($operation:literal, $index:expr, $uuid:expr) => {
+        #[cfg(feature = "otel")]
+        let _span = tracing::info_span!(
+            "glass.redis",
+            operation = $operation,
+            index = %$index,
+            uuid = %$uuid.to_simple()
+        )
+        .entered();
+    };
+}
 
 /// Redis Connection Config
 /// Supports both TCP and Socket connections.
@@ -28,12 +71,146 @@ pub struct RedisConfig {
     pub database_password: Option<String>,
 }
 
+/// How failed Redis calls are retried by with_retry.
+/// The delay before each retry doubles, starting from `base_delay`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The delay before the given retry, counting from zero.
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.base_delay
+            .checked_mul(2u32.saturating_pow(retry))
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// Whether an error may go away if the call is made again.
+/// Connection errors always may, Redis errors only when the connection failed rather than the command.
+fn is_transient(error: &GlassError) -> bool {
+    match error {
+        GlassError::Connection(_) => true,
+        GlassError::Redis(e) => {
+            e.is_io_error()
+                || e.is_timeout()
+                || e.is_connection_dropped()
+                || e.is_connection_refusal()
+        }
+        _ => false,
+    }
+}
+
+/// Calls `f` until it succeeds, retrying transient connection errors with exponential backoff.
+/// Other errors, and the last error once `max_retries` is used up, are returned as they are.
+/// `f` must be safe to call again after a failed call, see RetryingConnection::call.
+/// Only the error finally returned is passed to the error hook.
+pub fn with_retry<F, T>(config: &RetryConfig, f: F) -> Result<T, GlassError>
+where
+    F: FnMut() -> Result<T, GlassError>,
+{
+    retry_with_sleep(config, f, std::thread::sleep)
+}
+
+/// with_retry with the sleep between attempts injected, so the delays can be tested.
+pub(crate) fn retry_with_sleep<F, S, T>(
+    config: &RetryConfig,
+    mut f: F,
+    mut sleep: S,
+) -> Result<T, GlassError>
+where
+    F: FnMut() -> Result<T, GlassError>,
+    S: FnMut(Duration),
+{
+    let mut retry = 0;
+    loop {
+        match unreported(&mut f) {
+            Err(e) if retry < config.max_retries && is_transient(&e) => {
+                sleep(config.delay(retry));
+                retry += 1;
+            }
+            Err(e) => return Err(report(e)),
+            result => return result,
+        }
+    }
+}
+
+/// A Redis connection that is formed again between retries of a failed call.
+/// Created with RedisConfig::form_retrying_connection.
+pub struct RetryingConnection {
+    config: RedisConfig,
+    retry: RetryConfig,
+    connection: Option<Connection>,
+}
+
+impl RetryingConnection {
+    /// Calls `f` with the connection, forming it first if needed.
+    /// After a transient error the connection is dropped, so the next attempt forms a new one.
+    ///
+    /// `f` is called again after a transient error, even when its commands already reached
+    /// Redis before the connection failed. Only pass closures that are safe to run twice, like
+    /// reads, HSET of fixed values or ZADD of fixed scores, and not HINCRBY, INCR or pushes.
+    /// Only the error finally returned is passed to the error hook, not those of retried calls.
+    pub fn call<F, T>(&mut self, mut f: F) -> Result<T, GlassError>
+    where
+        F: FnMut(&mut Connection) -> Result<T, GlassError>,
+    {
+        let RetryingConnection {
+            config,
+            retry,
+            connection,
+        } = self;
+
+        with_retry(retry, || {
+            let mut current = match connection.take() {
+                Some(x) => x,
+                None => config.clone().form_connection()?,
+            };
+
+            let result = f(&mut current);
+            if !matches!(result, Err(ref e) if is_transient(e)) {
+                *connection = Some(current);
+            }
+            result
+        })
+    }
+}
+
 /// Function to parse a RedisConfig from a JSON file in a folder.
-pub fn parse_redis_config<T: AsRef<std::path::Path>>(
+pub fn parse_redis_config<T: AsRef<std::path::Path>>(path: T) -> Result<RedisConfig, GlassError> {
+    let file = std::fs::File::open(path)?;
+    serde_json::from_reader(file).map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Function to parse a RedisConfig from a gzip compressed JSON file in a folder.
+pub fn parse_redis_config_gz<T: AsRef<std::path::Path>>(
     path: T,
-) -> Result<RedisConfig, Box<dyn Error>> {
+) -> Result<RedisConfig, GlassError> {
     let file = std::fs::File::open(path)?;
-    Ok(serde_json::from_reader(file)?)
+    serde_json::from_reader(flate2::read::GzDecoder::new(file))
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Function to parse a RedisConfig from a JSON file in a folder.
+/// Files ending in `.gz` are decompressed first.
+pub fn parse_redis_config_auto<T: AsRef<std::path::Path>>(
+    path: T,
+) -> Result<RedisConfig, GlassError> {
+    match path.as_ref().extension() {
+        Some(extension) if extension == "gz" => parse_redis_config_gz(path),
+        _ => parse_redis_config(path),
+    }
 }
 
 impl Default for RedisConfig {
@@ -49,36 +226,123 @@ impl Default for RedisConfig {
 }
 
 impl RedisConfig {
+    /// Parses a RedisConfig from a URL like `redis://:password@host:port/db`.
+    /// Unix sockets are given as `unix:///path/to/socket?db=1`, which can't carry a password.
+    /// The redis crate used doesn't support TLS, so `rediss://` URLs are an error.
+    /// It doesn't support ACL users either, so URLs with a username are an error
+    /// rather than connecting as the default user.
+    pub fn from_url(url: &str) -> Result<RedisConfig, GlassError> {
+        if url.starts_with("rediss://") {
+            return Err(report(GlassError::Connection(RedisError::from((
+                ErrorKind::InvalidClientConfig,
+                "TLS connections are not supported",
+            )))));
+        }
+
+        if let Some(rest) = url.strip_prefix("redis://") {
+            let authority = rest.split(['/', '?']).next().unwrap_or("");
+            if let Some((userinfo, _)) = authority.rsplit_once('@') {
+                if !userinfo.split(':').next().unwrap_or("").is_empty() {
+                    return Err(report(GlassError::Connection(RedisError::from((
+                        ErrorKind::InvalidClientConfig,
+                        "usernames are not supported",
+                    )))));
+                }
+            }
+        }
+
+        let info = url
+            .into_connection_info()
+            .map_err(|e| report(GlassError::Connection(e)))?;
+        let (database_ip, database_port, database_socket) = match *info.addr {
+            ConnectionAddr::Tcp(host, port) => (Some(host), Some(port), None),
+            ConnectionAddr::Unix(path) => (None, None, Some(path.to_string_lossy().into_owned())),
+        };
+
+        Ok(RedisConfig {
+            database_ip,
+            database_port,
+            database_socket,
+            database_id: info.db,
+            database_password: info.passwd,
+        })
+    }
+
+    /// Creates a connection to the Redis database described by a URL.
+    /// See from_url for the URLs accepted.
+    pub fn form_connection_from_url(url: &str) -> Result<Connection, GlassError> {
+        RedisConfig::from_url(url)?.form_connection()
+    }
+
     /// Creates a connection to the Redis database using the RedisConfig
-    pub fn form_connection(self) -> Result<Connection, Box<dyn Error>> {
-        if self.database_socket.is_none() {
-            // Handle TCP Connection
+    pub fn form_connection(self) -> Result<Connection, GlassError> {
+        Client::open(self.connection_info())
+            .and_then(|client| client.get_connection())
+            .map_err(|e| report(GlassError::Connection(e)))
+    }
 
-            Ok(Client::open(ConnectionInfo {
-                addr: Box::new(ConnectionAddr::Tcp(
-                    self.database_ip
-                        .clone()
-                        .map_or("localhost".to_owned(), |x| x),
-                    self.database_port.map_or(6379, |x| x),
-                )),
-                db: self.database_id,
-                passwd: self.database_password.clone().and_then(Some),
-            })?
-            .get_connection()?)
-        } else {
+    /// Checks that the Redis database is reachable by forming a connection and sending a PING.
+    /// Returns the round trip time of the PING, not counting the time taken to connect.
+    pub fn check_connection(&self) -> Result<Duration, GlassError> {
+        let mut connection = self.clone().form_connection()?;
+
+        let start = Instant::now();
+        let _pong: String = redis::cmd("PING").query(&mut connection)?;
+
+        Ok(start.elapsed())
+    }
+
+    /// Creates a connection that is formed again and retried after transient errors.
+    /// The connection is formed on first use, so this never fails.
+    pub fn form_retrying_connection(self, retry: RetryConfig) -> RetryingConnection {
+        RetryingConnection {
+            config: self,
+            retry,
+            connection: None,
+        }
+    }
+
+    /// Creates an asynchronous connection to the Redis database using the RedisConfig
+    #[cfg(feature = "redis_async")]
+    pub async fn form_async_connection(self) -> Result<redis::aio::Connection, GlassError> {
+        use futures::compat::Future01CompatExt;
+
+        let client =
+            Client::open(self.connection_info()).map_err(|e| report(GlassError::Connection(e)))?;
+        client
+            .get_async_connection()
+            .compat()
+            .await
+            .map_err(|e| report(GlassError::Connection(e)))
+    }
+
+    /// Creates a multiplexed asynchronous connection to the Redis database using the RedisConfig
+    /// See MultiplexedConnection for how it differs from a pool of connections.
+    #[cfg(feature = "redis_async")]
+    pub async fn form_multiplexed(
+        self,
+    ) -> Result<super::redis_async::MultiplexedConnection, GlassError> {
+        let connection = self.clone().form_async_connection().await?;
+        super::redis_async::MultiplexedConnection::new(connection, self)
+    }
+
+    /// Builds the connection info for the database described by the RedisConfig.
+    fn connection_info(self) -> ConnectionInfo {
+        let addr = match self.database_socket {
             // Handle Socket Connection
+            Some(socket) => ConnectionAddr::Unix(PathBuf::from(socket)),
 
-            Ok(Client::open(ConnectionInfo {
-                addr: Box::new(ConnectionAddr::Unix(PathBuf::from_str(
-                    self.database_socket
-                        .clone()
-                        .map_or("/tmp/redis.sock".to_owned(), |x| x)
-                        .as_str(),
-                )?)),
-                db: self.database_id,
-                passwd: self.database_password.clone().and_then(Some),
-            })?
-            .get_connection()?)
+            // Handle TCP Connection
+            None => ConnectionAddr::Tcp(
+                self.database_ip.unwrap_or_else(|| "localhost".to_owned()),
+                self.database_port.unwrap_or(6379),
+            ),
+        };
+
+        ConnectionInfo {
+            addr: Box::new(addr),
+            db: self.database_id,
+            passwd: self.database_password,
         }
     }
 }
@@ -89,205 +353,2120 @@ pub fn insert_object_into_database<O>(
     connection: &mut Connection,
     object: O,
     uuid: Option<Uuid>,
-) -> Result<Uuid, Box<dyn Error>>
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable,
+{
+    insert_object_into_index(connection, object, uuid, None)
+}
+
+/// Function to normalize an object and insert it into a local Redis database.
+/// Returns the UUID of where the object is on the database.
+pub fn insert_normalized_object<O>(
+    connection: &mut Connection,
+    mut object: O,
+    uuid: Option<Uuid>,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable,
+{
+    object.normalize()?;
+    insert_object_into_database(connection, object, uuid)
+}
+
+/// Function to validate an object and insert it into a local Redis database.
+/// Returns the UUID of where the object is on the database, or the error of Sortable::validate.
+pub fn validated_insert<O>(
+    connection: &mut Connection,
+    object: O,
+    uuid: Option<Uuid>,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable,
+{
+    object.validate().map_err(report)?;
+    insert_object_into_database(connection, object, uuid)
+}
+
+/// Function to insert an object into a named index of a local Redis database.
+/// Uses the index of the object when no index is given.
+/// Returns the UUID of where the object is on the database.
+pub fn insert_object_into_index<O>(
+    connection: &mut Connection,
+    object: O,
+    uuid: Option<Uuid>,
+    index: Option<&str>,
+) -> Result<Uuid, GlassError>
 where
     O: Sortable,
 {
-    // Generate UUID or use provided one.
-    let gen_key = match uuid {
+    // Generate UUID or use the one the object or caller provides.
+    let gen_key = match object.primary_key().or(uuid) {
+        // The nil UUID is used as the empty index sentinel, so it can't be a key.
+        Some(k) if k.is_nil() => return Err(report(GlassError::InvalidKey(k))),
         Some(k) => k,
         None => Uuid::new_v4(),
     };
 
-    // Get Object Variables
-    let field_map: Vec<(String, O::DataType)> = object.object_to_map();
-    let index = O::object_to_index();
+    let index = index.unwrap_or(O::object_to_index());
+    operation_span!("insert_object_into_index", index, gen_key);
 
-    // Find next index in table.
-    let count: i32 = connection.zcard(&format!("{}-index", index))?;
+    // Objects already in the index keep their score, so only new objects take one.
+    let (score, stored): (Option<f64>, FieldMap<O::DataType>) = redis::pipe()
+        .zscore(format!("{}-index", index), gen_key.to_simple().to_string())
+        .hgetall(format!("{}:{}", index, gen_key.to_simple()))
+        .query(connection)?;
+    let score = match score {
+        Some(score) => score as i64,
+        None => reserve_scores(connection, index, 1)?,
+    };
 
-    // Generate a command pipeline.
-    let mut pipeline = redis::Pipeline::new();
+    // Finally send commands to database.
+    let stored = vec![(gen_key, stored)].into_iter().collect();
+    let commands = replacing_insert_commands(&[(gen_key, &object, score)], index, stored);
+    query_in_batches(connection, &commands, DEFAULT_MAX_PIPELINE_COMMANDS)?;
 
-    // Add UUID to index for object.
-    pipeline.add_command(
-        redis::cmd("ZADD")
-            .arg(&format!("{}-index", index))
-            .arg(count + 1)
-            .arg(&gen_key.to_simple().to_string())
-            .to_owned(),
-    );
+    Ok(gen_key)
+}
 
-    // Iterate through map to find fields that need to be populated and generate a command for them.
-    field_map.into_iter().for_each(|item| {
-        pipeline.add_command(
-            redis::cmd("HSET")
-                .arg(&format!("{}:{}", index, &gen_key.to_simple().to_string()))
-                .arg(item.0)
-                .arg(item.1)
-                .to_owned(),
-        );
-    });
+/// Function to insert an object into a local Redis database holding at most `capacity` objects.
+/// Evicts the least recently accessed objects to make room, counting the insert as an access.
+/// Only objects inserted with this function are tracked, so only they are evicted.
+/// Returns the UUID of where the object is on the database and the UUIDs of evicted objects.
+pub fn insert_with_capacity<O>(
+    connection: &mut Connection,
+    object: O,
+    uuid: Option<Uuid>,
+    capacity: usize,
+) -> Result<(Uuid, Vec<Uuid>), GlassError>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    let uuid = insert_object_into_database(connection, object, uuid)?;
+    operation_span!("insert_with_capacity", index, uuid);
 
-    // Finally send commands to database.
-    pipeline.query(connection)?;
+    let _res: i32 = connection.zadd(lru_key(index), uuid.to_simple().to_string(), unix_millis())?;
 
-    Ok(gen_key)
+    // Evict from the least recently accessed end until the index fits.
+    let count: i64 = connection.zcard(format!("{}-index", index))?;
+    let excess = count - capacity as i64;
+    if excess <= 0 {
+        return Ok((uuid, Vec::new()));
+    }
+
+    let output: Vec<String> = connection.zrange(lru_key(index), 0, excess as isize - 1)?;
+    let evicted = output
+        .iter()
+        .map(|x| Uuid::parse_str(x))
+        .collect::<Result<Vec<Uuid>, _>>()?;
+    remove_objects_from_database::<O>(connection, &evicted)?;
+
+    Ok((uuid, evicted))
 }
 
-/// Function to remove an object from a local Redis database.
-pub fn remove_object_from_database<O>(
+/// Function to insert many objects into a local Redis database in as few round trips as possible.
+/// Objects are given consecutive scores in the order they are passed in.
+/// Returns the UUIDs of where the objects are on the database, in insertion order.
+pub fn insert_objects_into_database<O>(
     connection: &mut Connection,
-    uuid: Uuid,
-) -> Result<(), Box<dyn Error>>
+    objects: Vec<(Option<Uuid>, O)>,
+) -> Result<Vec<Uuid>, GlassError>
 where
     O: Sortable,
 {
-    // Get Object Index
+    insert_objects_into_database_batched(connection, objects, DEFAULT_MAX_PIPELINE_COMMANDS)
+}
+
+/// Function to insert many objects into a local Redis database.
+/// The commands are sent in pipelines of at most `max_pipeline_commands` commands.
+/// Returns the UUIDs of where the objects are on the database, in insertion order.
+pub fn insert_objects_into_database_batched<O>(
+    connection: &mut Connection,
+    objects: Vec<(Option<Uuid>, O)>,
+    max_pipeline_commands: usize,
+) -> Result<Vec<Uuid>, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("insert_objects_into_database_batched", O::object_to_index());
+
     let index = O::object_to_index();
-    let index_id = format!("{}:{}", index, &uuid.to_simple().to_string());
 
-    // Generate a field map for the object.
-    let map: Vec<String> = connection.hkeys(&index_id)?;
+    // Objects carrying their own UUID are stored under it.
+    let objects: Vec<(Option<Uuid>, O)> = objects
+        .into_iter()
+        .map(|(uuid, object)| (object.primary_key().or(uuid), object))
+        .collect();
 
-    // Remove uuid in table.
-    let _res: i32 = connection.zrem(format!("{}-index", index), uuid.to_simple().to_string())?;
+    // The nil UUID is used as the empty index sentinel, so it can't be a key.
+    if let Some(uuid) = objects.iter().find_map(|x| x.0.filter(Uuid::is_nil)) {
+        return Err(report(GlassError::InvalidKey(uuid)));
+    }
 
-    // Generate a command pipeline.
-    let mut pipeline = redis::Pipeline::new();
+    // Only objects given a UUID can already be in the index.
+    let uuids: Vec<Uuid> = objects
+        .iter()
+        .map(|x| x.0.unwrap_or_else(Uuid::new_v4))
+        .collect();
+    let given: Vec<Uuid> = objects.iter().filter_map(|x| x.0).collect();
+    let scores: Vec<Option<f64>> = index_scores_pipeline(index, &given).query(connection)?;
+    let indexed: Vec<Uuid> = given
+        .iter()
+        .zip(scores)
+        .filter_map(|(uuid, score)| score.map(|_| *uuid))
+        .collect();
+    let first = match new_object_count(&uuids, &indexed) {
+        0 => 0,
+        count => reserve_scores(connection, index, count)?,
+    };
+    let scores = assign_scores(first, &uuids, &indexed);
 
-    // Iterate through map to find fields that need to be removed and generate a command for them.
-    map.into_iter().for_each(|item| {
-        pipeline.add_command(redis::cmd("HDEL").arg(&index_id).arg(item).to_owned());
-    });
+    // Grab the fields of objects being replaced, so their entries can be taken out of the sets.
+    let stored: Vec<FieldMap<O::DataType>> =
+        stored_fields_pipeline(index, &given).query(connection)?;
+    let stored = given.into_iter().zip(stored).collect();
+
+    let inserts: Vec<(Uuid, &O, i64)> = objects
+        .iter()
+        .zip(&uuids)
+        .zip(scores)
+        .map(|(((_, object), uuid), score)| (*uuid, object, score))
+        .collect();
+    let commands = replacing_insert_commands(&inserts, index, stored);
 
     // Finally send commands to database.
-    pipeline.query(connection)?;
+    query_in_batches(connection, &commands, max_pipeline_commands)?;
 
-    Ok(())
+    Ok(uuids)
 }
 
-/// Function to edit a field in an object in a local Redis database.
-pub fn edit_object_from_database<O>(
-    connection: &mut Connection,
-    changes: Vec<(String, O::DataType)>,
-    uuid: Uuid,
-) -> Result<(), Box<dyn Error>>
+/// Returns the commands needed to insert an object.
+pub(crate) fn insert_commands<O>(object: &O, uuid: Uuid, index: &str, score: i64) -> Vec<redis::Cmd>
 where
     O: Sortable,
 {
-    // Generate a command pipeline.
-    let mut pipeline = redis::Pipeline::new();
-
     // Get Object Variables
-    let index = O::object_to_index();
+    debug_assert!(
+        object.map_matches_fields(),
+        "object_to_map of {} does not match its fields",
+        index
+    );
 
-    // Iterate through map to find fields that need to be edited and generate a command for them.
-    changes.into_iter().for_each(|item| {
-        pipeline.add_command(
+    let field_map: Vec<(String, O::DataType)> = object.object_to_map();
+    let key = uuid.to_simple().to_string();
+    let mut commands = Vec::new();
+
+    // Add UUID to index for object, keeping the score of an object already in the index.
+    commands.push(
+        redis::cmd("ZADD")
+            .arg(format!("{}-index", index))
+            .arg("NX")
+            .arg(score)
+            .arg(&key)
+            .to_owned(),
+    );
+
+    // Record the schema version the object is stored at.
+    commands.push(
+        redis::cmd("HSET")
+            .arg(schema_version_key(index))
+            .arg(&key)
+            .arg(O::SCHEMA_VERSION)
+            .to_owned(),
+    );
+
+    // Add UUID to the type, tag, dependency count and names indexes for object.
+    commands.extend(SecondaryEntries::of(object, &field_map).add_commands(index, &key));
+
+    // Iterate through map to find fields that need to be populated and generate a command for them.
+    field_map.into_iter().for_each(|item| {
+        commands.push(
             redis::cmd("HSET")
-                .arg(&format!("{}:{}", index, &uuid.to_simple().to_string()))
+                .arg(format!("{}:{}", index, key))
                 .arg(item.0)
                 .arg(item.1)
                 .to_owned(),
         );
     });
 
-    // Finally send commands to database.
-    pipeline.query(connection)?;
+    // Tell subscribers about the new object.
+    #[cfg(feature = "pubsub")]
+    if O::publish_changes() {
+        commands.push(change_command(index, uuid, ChangeOp::Insert));
+    }
 
-    Ok(())
+    // Expire the object if its type has a time to live.
+    if let Some(ttl) = O::default_ttl() {
+        commands.push(
+            redis::cmd("EXPIRE")
+                .arg(format!("{}:{}", index, key))
+                .arg(ttl.as_secs().max(1))
+                .to_owned(),
+        );
+    }
+
+    commands
 }
 
-/// Function to retrieve a object in a local Redis database.
-pub fn retrieve_object_from_database<O>(
-    connection: &mut Connection,
-    uuid: Uuid,
-) -> Result<FieldMap<O::DataType>, Box<dyn Error>>
-where
-    O: Sortable,
-{
-    // Get Object Index
-    let index = format!("{}:{}", O::object_to_index(), &uuid.to_simple().to_string());
+/// The entries an object has in the type, tag, dependency count and names indexes of its index.
+pub(crate) struct SecondaryEntries {
+    item_type: Option<String>,
+    name: Option<String>,
+    tags: Vec<String>,
+    dependencies: Vec<Uuid>,
+}
 
-    // Generate a field map for the object.
-    let map: Vec<String> = connection.hkeys(&index)?;
+impl SecondaryEntries {
+    /// Reads the entries of an object from it and the fields it is stored with.
+    pub(crate) fn of<O>(object: &O, field_map: &[(String, O::DataType)]) -> Self
+    where
+        O: Sortable,
+    {
+        let item_type = O::type_field()
+            .and_then(|field| field_map.iter().find(|item| item.0 == field))
+            .map(|item| field_string(&item.1));
 
-    // Iterate through map and grab the value corrosponding to the key from the database and store it.
-    let object: HashMap<String, O::DataType> = map
-        .into_iter()
-        .map(|key| {
-            let value: O::DataType = connection
-                .hget(&index, &key)
-                .expect("Could not get value from key for object.");
+        SecondaryEntries {
+            item_type,
+            name: stored_name::<O>(field_map),
+            tags: object.tag_values(),
+            dependencies: object.dependency_keys(),
+        }
+    }
 
-            (key, value)
-        })
-        .collect();
+    /// Reads the entries of an object from its stored fields.
+    pub(crate) fn stored<O>(stored: FieldMap<O::DataType>) -> Self
+    where
+        O: Sortable,
+    {
+        let field_map: Vec<(String, O::DataType)> = stored
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        SecondaryEntries::of(&O::map_to_object(stored), &field_map)
+    }
 
-    Ok(object)
-}
+    /// Returns the entries not in `other`, counting a repeated dependency once per repeat.
+    pub(crate) fn without(&self, other: &SecondaryEntries) -> Self {
+        let mut dependencies = other.dependencies.clone();
 
-/// Function to request a range of objects from a local Redis database.
-/// Returns the objects from the database with the key and object in a Vec.
-pub fn request_group_of_objects<O>(
-    connection: &mut Connection,
-    amount: isize,
-) -> RedisResult<O::DataType>
-where
-    O: Sortable + Clone,
-{
-    let output: Vec<String> = connection.zrange(
-        &format!("{}-index", O::object_to_index()),
-        8 * (amount - 1),
-        (8 * amount) - 1,
-    )?;
+        SecondaryEntries {
+            item_type: self
+                .item_type
+                .clone()
+                .filter(|x| other.item_type.as_ref() != Some(x)),
+            name: self.name.clone().filter(|x| other.name.as_ref() != Some(x)),
+            tags: self
+                .tags
+                .iter()
+                .filter(|x| !other.tags.contains(x))
+                .cloned()
+                .collect(),
+            dependencies: self
+                .dependencies
+                .iter()
+                .filter(|x| match dependencies.iter().position(|y| y == *x) {
+                    Some(position) => {
+                        dependencies.remove(position);
+                        false
+                    }
+                    None => true,
+                })
+                .copied()
+                .collect(),
+        }
+    }
 
-    Ok(output
-        .into_iter()
-        .map(|x| {
-            let uuid = Uuid::parse_str(&x).expect("Failed to parse UUID from object.");
-            let object: FieldMap<O::DataType> =
-                retrieve_object_from_database::<O>(connection, uuid)
-                    .expect("Failed to retrieve object from the database.");
+    /// Returns the commands adding the object under `key` to the indexes.
+    pub(crate) fn add_commands(&self, index: &str, key: &str) -> Vec<redis::Cmd> {
+        let mut commands = Vec::new();
 
-            (uuid, object)
-        })
-        .collect())
+        if let Some(item_type) = &self.item_type {
+            commands.push(
+                redis::cmd("SADD")
+                    .arg(type_set_key(index, item_type))
+                    .arg(key)
+                    .to_owned(),
+            );
+        }
+
+        // Names are all scored 0, so they sort lexicographically.
+        if let Some(name) = &self.name {
+            commands.push(
+                redis::cmd("ZADD")
+                    .arg(name_key(index))
+                    .arg(0)
+                    .arg(name_member(name, key))
+                    .to_owned(),
+            );
+        }
+
+        self.tags.iter().for_each(|tag| {
+            commands.push(
+                redis::cmd("SADD")
+                    .arg(tag_set_key(index, tag))
+                    .arg(key)
+                    .to_owned(),
+            );
+        });
+
+        // Count the object as a dependent of each of its dependencies.
+        self.dependencies.iter().for_each(|dependency| {
+            commands.push(
+                redis::cmd("HINCRBY")
+                    .arg(dependency_count_key(index))
+                    .arg(dependency.to_simple().to_string())
+                    .arg(1)
+                    .to_owned(),
+            );
+        });
+
+        commands
+    }
+
+    /// Returns the commands taking the object under `key` out of the indexes.
+    pub(crate) fn remove_commands(&self, index: &str, key: &str) -> Vec<redis::Cmd> {
+        let mut commands = Vec::new();
+
+        if let Some(item_type) = &self.item_type {
+            commands.push(
+                redis::cmd("SREM")
+                    .arg(type_set_key(index, item_type))
+                    .arg(key)
+                    .to_owned(),
+            );
+        }
+
+        if let Some(name) = &self.name {
+            commands.push(
+                redis::cmd("ZREM")
+                    .arg(name_key(index))
+                    .arg(name_member(name, key))
+                    .to_owned(),
+            );
+        }
+
+        self.tags.iter().for_each(|tag| {
+            commands.push(
+                redis::cmd("SREM")
+                    .arg(tag_set_key(index, tag))
+                    .arg(key)
+                    .to_owned(),
+            );
+        });
+
+        // Stop counting the object as a dependent of its dependencies.
+        self.dependencies.iter().for_each(|dependency| {
+            commands.push(
+                redis::cmd("HINCRBY")
+                    .arg(dependency_count_key(index))
+                    .arg(dependency.to_simple().to_string())
+                    .arg(-1)
+                    .to_owned(),
+            );
+        });
+
+        commands
+    }
 }
 
-/// Function to request all the objects from a local Redis database.
-/// Returns the objects from the database with the key and object in a Vec.
-pub fn request_all_objects<O>(connection: &mut Connection) -> RedisResult<O::DataType>
+/// Returns the commands needed to insert objects at their scores, in order.
+/// An object stored under a UUID that is already taken, by `stored` or earlier in the batch,
+/// first takes the object it replaces out of the type, tag, dependency count and names indexes.
+pub(crate) fn replacing_insert_commands<O>(
+    inserts: &[(Uuid, &O, i64)],
+    index: &str,
+    stored: HashMap<Uuid, FieldMap<O::DataType>>,
+) -> Vec<redis::Cmd>
 where
-    O: Sortable + Clone,
+    O: Sortable,
 {
-    let output: Vec<String> =
-        connection.zrange(&format!("{}-index", O::object_to_index()), 0, -1)?;
-
-    Ok(output
+    let mut replaced: HashMap<Uuid, (Option<SecondaryEntries>, Vec<String>)> = stored
         .into_iter()
-        .map(|x| {
-            let uuid = Uuid::parse_str(&x).expect("Failed to parse UUID from object.");
-            let object: FieldMap<O::DataType> =
-                retrieve_object_from_database::<O>(connection, uuid)
-                    .expect("Failed to retrieve object from the database.");
+        .filter(|(_, map)| !map.is_empty())
+        .map(|(uuid, map)| {
+            let keys = map.keys().cloned().collect();
 
-            (uuid, object)
+            // Soft deleted objects were already taken out of the sets.
+            let entries = if map.contains_key(DELETED_FIELD) {
+                None
+            } else {
+                Some(SecondaryEntries::stored::<O>(map))
+            };
+            (uuid, (entries, keys))
         })
-        .collect())
-}
+        .collect();
+
+    let mut commands = Vec::new();
+    for (uuid, object, score) in inserts {
+        let key = uuid.to_simple().to_string();
+        let field_map = object.object_to_map();
+
+        if let Some((entries, keys)) = replaced.remove(uuid) {
+            commands.extend(entries.iter().flat_map(|x| x.remove_commands(index, &key)));
+
+            // The replaced fields take the insert as their last change.
+            commands.push(redis::cmd("DEL").arg(mtime_key(index, *uuid)).to_owned());
+
+            // Extra entries left behind would be read back as part of the new object.
+            let mut stale = stale_extra_fields(&keys, &field_map);
+            if keys.iter().any(|x| x == DELETED_FIELD) {
+                stale.push(DELETED_FIELD.to_owned());
+            }
+            if !stale.is_empty() {
+                commands.push(
+                    redis::cmd("HDEL")
+                        .arg(format!("{}:{}", index, key))
+                        .arg(stale)
+                        .to_owned(),
+                );
+            }
+        }
+
+        commands.extend(insert_commands(*object, *uuid, index, *score));
+        let keys = field_map.iter().map(|x| x.0.clone()).collect();
+        replaced.insert(
+            *uuid,
+            (Some(SecondaryEntries::of(*object, &field_map)), keys),
+        );
+    }
+
+    commands
+}
+
+/// Returns the stored extra entries that a field map replacing them doesn't have.
+fn stale_extra_fields<T>(stored: &[String], field_map: &[(String, T)]) -> Vec<String> {
+    stored
+        .iter()
+        .filter(|key| key.starts_with(EXTRA_PREFIX))
+        .filter(|key| !field_map.iter().any(|x| &x.0 == *key))
+        .cloned()
+        .collect()
+}
+
+/// Builds a pipeline reading the stored fields of each UUID.
+pub(crate) fn stored_fields_pipeline(index: &str, uuids: &[Uuid]) -> redis::Pipeline {
+    let mut pipeline = redis::pipe();
+    for uuid in uuids {
+        pipeline.hgetall(format!("{}:{}", index, uuid.to_simple()));
+    }
+    pipeline
+}
+
+/// Builds a pipeline looking up the index score of each UUID.
+pub(crate) fn index_scores_pipeline(index: &str, uuids: &[Uuid]) -> redis::Pipeline {
+    let mut pipeline = redis::pipe();
+    for uuid in uuids {
+        pipeline
+            .cmd("ZSCORE")
+            .arg(format!("{}-index", index))
+            .arg(uuid.to_simple().to_string());
+    }
+    pipeline
+}
+
+/// Counts the objects in a batch that will take a new score.
+/// Objects already in the index, or repeated in the batch, keep their score through ZADD NX.
+pub(crate) fn new_object_count(uuids: &[Uuid], indexed: &[Uuid]) -> usize {
+    let mut seen: Vec<Uuid> = indexed.to_vec();
+    uuids
+        .iter()
+        .filter(|uuid| {
+            let new = !seen.contains(uuid);
+            if new {
+                seen.push(**uuid);
+            }
+            new
+        })
+        .count()
+}
+
+/// Gives each new object the next of the scores reserved from `first`, in batch order.
+/// Objects already in the index, or repeated in the batch, keep their score through ZADD NX,
+/// so they don't take up a score.
+pub(crate) fn assign_scores(first: i64, uuids: &[Uuid], indexed: &[Uuid]) -> Vec<i64> {
+    let mut seen: Vec<Uuid> = indexed.to_vec();
+    let mut next = first - 1;
+
+    uuids
+        .iter()
+        .map(|uuid| {
+            if !seen.contains(uuid) {
+                seen.push(*uuid);
+                next += 1;
+            }
+            next
+        })
+        .collect()
+}
+
+/// Builds the command reading the highest score in an index.
+pub(crate) fn top_score_cmd(index: &str) -> redis::Cmd {
+    redis::cmd("ZREVRANGE")
+        .arg(format!("{}-index", index))
+        .arg(0)
+        .arg(0)
+        .arg("WITHSCORES")
+        .to_owned()
+}
+
+/// Builds a pipeline taking `count` scores from the counter of an index, returning the last.
+/// A missing counter starts from the highest score in the index, so indexes written before
+/// the counter existed carry on after their last object.
+pub(crate) fn reserve_scores_pipeline(
+    index: &str,
+    top: &[(String, f64)],
+    count: usize,
+) -> redis::Pipeline {
+    let top = top.first().map_or(0, |x| x.1 as i64);
+    let mut pipeline = redis::pipe();
+    pipeline
+        .cmd("SETNX")
+        .arg(counter_key(index))
+        .arg(top)
+        .ignore()
+        .cmd("INCRBY")
+        .arg(counter_key(index))
+        .arg(count);
+    pipeline
+}
+
+/// Function to take `count` unused scores from the counter of an index in a local Redis database.
+/// Scores only ever grow, so a score is never given out twice, even after objects are removed.
+/// Returns the first of the reserved scores.
+pub fn reserve_scores(
+    connection: &mut Connection,
+    index: &str,
+    count: usize,
+) -> Result<i64, GlassError> {
+    let top: Vec<(String, f64)> = top_score_cmd(index).query(connection)?;
+    let (last,): (i64,) = reserve_scores_pipeline(index, &top, count).query(connection)?;
+    Ok(last - count as i64 + 1)
+}
+
+/// Converts a stored field value into the string Redis holds for it.
+fn field_string<T: redis::ToRedisArgs>(value: &T) -> String {
+    String::from_utf8_lossy(&value.to_redis_args().concat()).into_owned()
+}
+
+/// Function to remove an object from a local Redis database.
+pub fn remove_object_from_database<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    remove_object_from_index::<O>(connection, uuid, None)
+}
+
+/// Function to remove an object from a named index of a local Redis database.
+/// Uses the index of the object when no index is given.
+pub fn remove_object_from_index<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    index: Option<&str>,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    operation_span!(
+        "remove_object_from_index",
+        index.unwrap_or(O::object_to_index()),
+        uuid
+    );
+
+    // Finally send commands to database.
+    let index = index.unwrap_or(O::object_to_index());
+    let commands = remove_commands::<O>(connection, uuid, index)?;
+    query_in_batches(connection, &commands, DEFAULT_MAX_PIPELINE_COMMANDS)
+}
+
+/// Function to remove an object from a local Redis database, even when it is half stored.
+/// Objects that are indexed without their fields, or stored without being indexed, are cleaned
+/// up instead of failing with HashMissing or IndexOrphan. Objects that are neither indexed nor
+/// stored still fail with NotIndexed. The type, tag and names entries of an object whose
+/// fields are gone can't be looked up, so only its index entries are removed.
+pub fn force_remove_object_from_database<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("force_remove_object_from_database", index, uuid);
+
+    let (score, stored, map): (Option<f64>, bool, FieldMap<O::DataType>) = redis::pipe()
+        .zscore(format!("{}-index", index), uuid.to_simple().to_string())
+        .exists(format!("{}:{}", index, uuid.to_simple()))
+        .hgetall(format!("{}:{}", index, uuid.to_simple()))
+        .query(connection)?;
+    if score.is_none() && !stored {
+        return Err(report(GlassError::NotIndexed(uuid)));
+    }
+
+    // Finally send commands to database.
+    let commands = removal_commands::<O>(uuid, index, map);
+    query_in_batches(connection, &commands, DEFAULT_MAX_PIPELINE_COMMANDS)
+}
+
+/// Function to remove many objects from a local Redis database.
+/// Objects which don't exist are skipped, while a half stored object fails the whole removal
+/// with HashMissing or IndexOrphan before anything is removed.
+/// Returns how many objects were removed, counting a repeated UUID once.
+pub fn remove_objects_from_database<O>(
+    connection: &mut Connection,
+    uuids: &[Uuid],
+) -> Result<usize, GlassError>
+where
+    O: Sortable,
+{
+    remove_objects_from_database_batched::<O>(connection, uuids, DEFAULT_MAX_PIPELINE_COMMANDS)
+}
+
+/// Function to remove many objects from a local Redis database.
+/// The objects are read in pipelines of at most `max_pipeline_commands` commands, then removed
+/// in one MULTI/EXEC. The index and objects are watched while they are read, so the objects
+/// counted are the objects removed. Returns how many objects were removed.
+pub fn remove_objects_from_database_batched<O>(
+    connection: &mut Connection,
+    uuids: &[Uuid],
+    max_pipeline_commands: usize,
+) -> Result<usize, GlassError>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("remove_objects_from_database_batched", index);
+
+    let mut unique: Vec<Uuid> = Vec::with_capacity(uuids.len());
+    for uuid in uuids {
+        if !unique.contains(uuid) {
+            unique.push(*uuid);
+        }
+    }
+
+    let mut keys = vec![format!("{}-index", index)];
+    keys.extend(
+        unique
+            .iter()
+            .map(|uuid| format!("{}:{}", index, uuid.to_simple())),
+    );
+
+    let mut removed = 0;
+    with_transaction::<(), _>(connection, &keys, |connection, pipeline| {
+        // Skip the objects which don't exist and make sure the rest are in a consistent state.
+        let mut present = Vec::with_capacity(unique.len());
+        for batch in unique.chunks((max_pipeline_commands / 3).max(1)) {
+            let states: Vec<(Option<f64>, bool, bool)> =
+                object_states_pipeline(index, batch).query(connection)?;
+
+            for (uuid, state) in batch.iter().zip(states) {
+                if state.0.is_some() || state.1 {
+                    object_state(*uuid, state)?;
+                    present.push(*uuid);
+                }
+            }
+        }
+
+        // Grab the fields of every object so their sets and counts can be updated.
+        for batch in present.chunks(max_pipeline_commands.max(1)) {
+            let maps: Vec<FieldMap<O::DataType>> =
+                stored_fields_pipeline(index, batch).query(connection)?;
+
+            for (uuid, map) in batch.iter().zip(maps) {
+                for command in removal_commands::<O>(*uuid, index, map) {
+                    pipeline.add_command(command).ignore();
+                }
+            }
+        }
+
+        removed = present.len();
+        Ok(())
+    })?;
+
+    Ok(removed)
+}
+
+/// Returns the commands needed to remove an object, reading what it needs from the database.
+fn remove_commands<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    index: &str,
+) -> Result<Vec<redis::Cmd>, GlassError>
+where
+    O: Sortable,
+{
+    // Grab the fields of the object so its sets and counts can be updated.
+    let map = retrieve_object_from_index::<O>(connection, uuid, Some(index))?;
+
+    Ok(removal_commands::<O>(uuid, index, map))
+}
+
+/// Returns the commands needed to remove an object from its stored fields.
+pub(crate) fn removal_commands<O>(
+    uuid: Uuid,
+    index: &str,
+    stored: FieldMap<O::DataType>,
+) -> Vec<redis::Cmd>
+where
+    O: Sortable,
+{
+    let index_id = format!("{}:{}", index, &uuid.to_simple().to_string());
+    let key = uuid.to_simple().to_string();
+    let map: Vec<String> = stored.keys().cloned().collect();
+    let mut commands = Vec::new();
+
+    // Remove uuid in table.
+    commands.push(
+        redis::cmd("ZREM")
+            .arg(format!("{}-index", index))
+            .arg(&key)
+            .to_owned(),
+    );
+    commands.push(redis::cmd("ZREM").arg(lru_key(index)).arg(&key).to_owned());
+    commands.push(
+        redis::cmd("ZREM")
+            .arg(popularity_key(index))
+            .arg(&key)
+            .to_owned(),
+    );
+    commands.push(
+        redis::cmd("HDEL")
+            .arg(schema_version_key(index))
+            .arg(&key)
+            .to_owned(),
+    );
+
+    // Remove uuid from the type, tag, dependency count and names indexes for object.
+    // Soft deleted objects were already taken out of them.
+    if !stored.contains_key(DELETED_FIELD) {
+        commands.extend(SecondaryEntries::stored::<O>(stored).remove_commands(index, &key));
+    }
+
+    // Iterate through map to find fields that need to be removed and generate a command for them.
+    map.into_iter().for_each(|item| {
+        commands.push(redis::cmd("HDEL").arg(&index_id).arg(item).to_owned());
+    });
+    commands.push(redis::cmd("DEL").arg(mtime_key(index, uuid)).to_owned());
+
+    // Tell subscribers the object is gone.
+    #[cfg(feature = "pubsub")]
+    if O::publish_changes() {
+        commands.push(change_command(index, uuid, ChangeOp::Remove));
+    }
+
+    commands
+}
+
+/// Sends commands to the database in pipelines of at most `max_commands` commands.
+/// Keeps large batches under the output buffer limits of the Redis server.
+fn query_in_batches(
+    connection: &mut Connection,
+    commands: &[redis::Cmd],
+    max_commands: usize,
+) -> Result<(), GlassError> {
+    for pipeline in batch_pipelines(commands, max_commands) {
+        pipeline.query::<()>(connection)?;
+    }
+
+    Ok(())
+}
+
+/// Splits commands into pipelines of at most `max_commands` commands.
+pub(crate) fn batch_pipelines(
+    commands: &[redis::Cmd],
+    max_commands: usize,
+) -> Vec<redis::Pipeline> {
+    commands
+        .chunks(max_commands.max(1))
+        .map(|batch| {
+            let mut pipeline = redis::Pipeline::with_capacity(batch.len());
+            batch.iter().for_each(|command| {
+                pipeline.add_command(command.clone());
+            });
+            pipeline
+        })
+        .collect()
+}
+
+/// Function to soft delete an object from a local Redis database.
+/// The object is flagged as deleted and removed from the index, but its fields are kept.
+/// Returns NotIndexed for objects that are neither in the index nor stored.
+pub fn soft_delete_object<O>(connection: &mut Connection, uuid: Uuid) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("soft_delete_object", O::object_to_index(), uuid);
+
+    let index = O::object_to_index();
+    let index_id = format!("{}:{}", index, uuid.to_simple());
+    let key = uuid.to_simple().to_string();
+
+    // Objects out of the index were already taken out of its sets.
+    let score: Option<f64> = connection.zscore(format!("{}-index", index), &key)?;
+    let mut pipeline = redis::pipe();
+    if score.is_some() {
+        let stored: FieldMap<O::DataType> = connection.hgetall(&index_id)?;
+        for command in SecondaryEntries::stored::<O>(stored).remove_commands(index, &key) {
+            pipeline.add_command(command).ignore();
+        }
+    } else if !connection.exists::<_, bool>(&index_id)? {
+        // Flagging an unknown object would leave a hash holding only the flag.
+        return Err(report(GlassError::NotIndexed(uuid)));
+    }
+
+    pipeline
+        .atomic()
+        .hset(&index_id, DELETED_FIELD, true)
+        .ignore()
+        .zrem(format!("{}-index", index), &key)
+        .ignore()
+        .query::<()>(connection)?;
+
+    Ok(())
+}
+
+/// Function to restore a soft deleted object in a local Redis database.
+/// The deleted flag is cleared and the object is added back to the end of the index and its sets.
+/// Objects that aren't soft deleted are left as they are.
+pub fn restore_object<O>(connection: &mut Connection, uuid: Uuid) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("restore_object", O::object_to_index(), uuid);
+
+    let index = O::object_to_index();
+    let index_id = format!("{}:{}", index, uuid.to_simple());
+    let key = uuid.to_simple().to_string();
+
+    // The fields are gone, so there is nothing to restore.
+    let mut stored: FieldMap<O::DataType> = connection.hgetall(&index_id)?;
+    if stored.is_empty() {
+        return Err(report(GlassError::NotFound(uuid)));
+    }
+    if stored.remove(DELETED_FIELD).is_none() {
+        return Ok(());
+    }
+
+    // Take a new score, so the object can't share one with an object inserted meanwhile.
+    let score = reserve_scores(connection, index, 1)?;
+
+    let mut pipeline = redis::pipe();
+    for command in SecondaryEntries::stored::<O>(stored).add_commands(index, &key) {
+        pipeline.add_command(command).ignore();
+    }
+
+    pipeline
+        .atomic()
+        .hdel(&index_id, DELETED_FIELD)
+        .ignore()
+        .zadd(format!("{}-index", index), &key, score)
+        .ignore()
+        .query::<()>(connection)?;
+
+    Ok(())
+}
+
+/// Function to duplicate an object in a named index of a local Redis database.
+/// The copy is added to the end of the index under a new UUID, which is returned.
+pub fn duplicate_object<O>(
+    connection: &mut Connection,
+    index: &str,
+    source: Uuid,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("duplicate_object", index, source);
+
+    // Read the object back so the copy also joins the type and tag sets.
+    let object = O::map_to_object(retrieve_object_from_index::<O>(
+        connection,
+        source,
+        Some(index),
+    )?);
+
+    insert_object_into_index(connection, object, None, Some(index))
+}
+
+/// Function to edit a field in an object in a local Redis database.
+/// Returns NotFound when the object isn't in the index.
+pub fn edit_object_from_database<O>(
+    connection: &mut Connection,
+    changes: Vec<(String, O::DataType)>,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    if !object_exists::<O>(connection, uuid)? {
+        return Err(report(GlassError::NotFound(uuid)));
+    }
+
+    edit_object_unchecked::<O>(connection, changes, uuid)
+}
+
+/// Function to edit an object in a local Redis database, inserting it if it isn't in the index.
+/// An edited object keeps its index score, while an inserted object gets the next score.
+pub fn upsert_object<O>(
+    connection: &mut Connection,
+    object: O,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("upsert_object", O::object_to_index(), uuid);
+
+    if object_exists::<O>(connection, uuid)? {
+        let index_id = format!("{}:{}", O::object_to_index(), uuid.to_simple());
+        let stored: FieldMap<O::DataType> = connection.hgetall(&index_id)?;
+        let keys: Vec<String> = stored.keys().cloned().collect();
+        let field_map = object.object_to_map();
+
+        // Only the fields whose value changed are written, so only they get a new edit time.
+        let changes = field_map
+            .iter()
+            .filter(|(key, value)| stored.get(key).map(field_string) != Some(field_string(value)))
+            .cloned()
+            .collect();
+        edit_object_unchecked::<O>(connection, changes, uuid)?;
+
+        // Extra entries left behind would be read back as part of the new object.
+        let stale = stale_extra_fields(&keys, &field_map);
+        if !stale.is_empty() {
+            redis::pipe()
+                .hdel(&index_id, stale.as_slice())
+                .ignore()
+                .hdel(mtime_key(O::object_to_index(), uuid), stale.as_slice())
+                .ignore()
+                .query::<()>(connection)?;
+        }
+
+        Ok(())
+    } else {
+        insert_object_into_database(connection, object, Some(uuid)).map(|_| ())
+    }
+}
+
+/// Function to edit a field in an object in a local Redis database without checking it exists.
+/// Editing a missing object stores the changed fields without adding it to the index.
+pub fn edit_object_unchecked<O>(
+    connection: &mut Connection,
+    changes: Vec<(String, O::DataType)>,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("edit_object_unchecked", O::object_to_index(), uuid);
+
+    // Generate a command pipeline.
+    let mut pipeline = redis::Pipeline::new();
+
+    // Get Object Variables
+    let index = O::object_to_index();
+    let index_id = format!("{}:{}", index, &uuid.to_simple().to_string());
+
+    // Grab the stored fields, so the object can be moved between the sets the edit changes.
+    // Missing and soft deleted objects aren't in the sets, so they are left out of them.
+    let stored: FieldMap<O::DataType> = connection
+        .hgetall(&index_id)
+        .map_err(type_checked(&index_id, "hash"))?;
+    let moved = if stored.is_empty() || stored.contains_key(DELETED_FIELD) {
+        None
+    } else {
+        let mut edited = stored.clone();
+        edited.extend(changes.iter().cloned());
+        Some((
+            SecondaryEntries::stored::<O>(stored),
+            SecondaryEntries::stored::<O>(edited),
+        ))
+    };
+
+    // Iterate through map to find fields that need to be edited and generate a command for them.
+    // Edit times are kept out of the object, so they don't change its fields or its ETag.
+    let now = unix_millis();
+    changes.into_iter().for_each(|item| {
+        pipeline.add_command(
+            redis::cmd("HSET")
+                .arg(mtime_key(index, uuid))
+                .arg(&item.0)
+                .arg(now)
+                .to_owned(),
+        );
+        pipeline.add_command(
+            redis::cmd("HSET")
+                .arg(&index_id)
+                .arg(item.0)
+                .arg(item.1)
+                .to_owned(),
+        );
+    });
+
+    // Move the UUID between the type, tag, dependency count and names indexes it changed.
+    if let Some((old, new)) = moved {
+        let key = uuid.to_simple().to_string();
+        old.without(&new)
+            .remove_commands(index, &key)
+            .into_iter()
+            .chain(new.without(&old).add_commands(index, &key))
+            .for_each(|command| {
+                pipeline.add_command(command);
+            });
+    }
+
+    // Tell subscribers the object was edited.
+    #[cfg(feature = "pubsub")]
+    if O::publish_changes() {
+        pipeline.add_command(change_command(index, uuid, ChangeOp::Edit));
+    }
+
+    // Finally send commands to database.
+    pipeline
+        .atomic()
+        .query::<()>(connection)
+        .map_err(type_checked(&index_id, "hash"))?;
+
+    Ok(())
+}
+
+/// Function to check if an object is in the index of a local Redis database.
+pub fn object_exists<O>(connection: &mut Connection, uuid: Uuid) -> Result<bool, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("object_exists", O::object_to_index(), uuid);
+
+    let score: Option<f64> = connection.zscore(
+        format!("{}-index", O::object_to_index()),
+        uuid.to_simple().to_string(),
+    )?;
+    Ok(score.is_some())
+}
+
+/// Function to retrieve a object in a local Redis database.
+pub fn retrieve_object_from_database<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+) -> Result<FieldMap<O::DataType>, GlassError>
+where
+    O: Sortable,
+{
+    retrieve_object_from_index::<O>(connection, uuid, None)
+}
+
+/// Function to retrieve an object in a local Redis database as the object itself.
+/// Objects stored at an older schema version are migrated before conversion.
+/// The migration isn't written back, so the stored object keeps its version until reinserted.
+pub fn get_object<O>(connection: &mut Connection, uuid: Uuid) -> Result<O, GlassError>
+where
+    O: Sortable,
+{
+    let mut map = retrieve_object_from_database::<O>(connection, uuid)?;
+
+    // Objects stored before versions were recorded are counted as the first version.
+    let version = schema_version::<O>(connection, uuid)?.unwrap_or(1);
+
+    if version < O::SCHEMA_VERSION {
+        O::migrate_object(&mut map, version)?;
+    }
+
+    Ok(O::map_to_object(map))
+}
+
+/// Function to return the schema version an object is stored at.
+/// Objects stored before versions were recorded return None.
+pub fn schema_version<O>(connection: &mut Connection, uuid: Uuid) -> Result<Option<u32>, GlassError>
+where
+    O: Sortable,
+{
+    Ok(connection.hget(
+        schema_version_key(O::object_to_index()),
+        uuid.to_simple().to_string(),
+    )?)
+}
+
+/// Function to retrieve a object in a local Redis database, optionally recording the access.
+/// Tracked accesses decide which object insert_with_capacity evicts, at the cost of a write.
+/// Only objects inserted by insert_with_capacity record their accesses, so reads never start
+/// tracking other objects or bring back objects removed while being read.
+pub fn retrieve_object_tracked<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    track_access: bool,
+) -> Result<FieldMap<O::DataType>, GlassError>
+where
+    O: Sortable,
+{
+    let object = retrieve_object_from_database::<O>(connection, uuid)?;
+
+    if track_access {
+        let _res: i32 = redis::cmd("ZADD")
+            .arg(lru_key(O::object_to_index()))
+            .arg("XX")
+            .arg(unix_millis())
+            .arg(uuid.to_simple().to_string())
+            .query(connection)?;
+    }
+
+    Ok(object)
+}
+
+/// Function to retrieve a object from a named index of a local Redis database.
+/// Uses the index of the object when no index is given.
+pub fn retrieve_object_from_index<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    index: Option<&str>,
+) -> Result<FieldMap<O::DataType>, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!(
+        "retrieve_object_from_index",
+        index.unwrap_or(O::object_to_index()),
+        uuid
+    );
+
+    // Make sure the object is in a consistent state.
+    let index = index.unwrap_or(O::object_to_index());
+    check_object_state(connection, index, uuid)?;
+
+    // Get Object Index
+    let index = format!("{}:{}", index, &uuid.to_simple().to_string());
+
+    // Read every field of the object at once.
+    let object: FieldMap<O::DataType> = connection
+        .hgetall(&index)
+        .map_err(type_checked(&index, "hash"))?;
+
+    Ok(object)
+}
+
+/// Function to retrieve when each field of an object in a local Redis database was last edited.
+/// Returns milliseconds since the Unix epoch, keyed by field name.
+/// Fields that haven't been edited since the object was inserted are left out.
+pub fn field_mtimes<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+) -> Result<HashMap<String, i64>, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("field_mtimes", O::object_to_index(), uuid);
+
+    Ok(connection.hgetall(mtime_key(O::object_to_index(), uuid))?)
+}
+
+/// Function to list the names of the fields stored for an object in a named index.
+/// Lists every stored field, including ones the object doesn't know about.
+pub fn stored_field_names(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<Vec<String>, GlassError> {
+    operation_span!("stored_field_names", index, uuid);
+
+    Ok(connection.hkeys(format!("{}:{}", index, uuid.to_simple()))?)
+}
+
+/// Function to retrieve a single field of an object in a local Redis database.
+/// Returns None when the field isn't set on the object.
+pub fn resolve_field<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    field: &str,
+) -> Result<Option<String>, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("resolve_field", O::object_to_index(), uuid);
+
+    let index_id = format!("{}:{}", O::object_to_index(), uuid.to_simple());
+    Ok(connection.hget(index_id, field)?)
+}
+
+/// Function to retrieve a object in a local Redis database along with its content hash.
+/// The hash only changes when the stored fields change, so it can be used as an ETag.
+pub fn retrieve_with_etag<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+) -> Result<(O, String), GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("retrieve_with_etag", O::object_to_index(), uuid);
+
+    let map = retrieve_object_from_database::<O>(connection, uuid)?;
+    let etag = content_hash(&map);
+
+    Ok((O::map_to_object(map), etag))
+}
+
+/// Function to retrieve a object in a local Redis database only if it no longer matches the ETag.
+/// Returns None when the stored object hashes to the supplied ETag.
+pub fn retrieve_if_changed<O>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    etag: &str,
+) -> Result<Option<(O, String)>, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("retrieve_if_changed", O::object_to_index(), uuid);
+
+    let (object, current) = retrieve_with_etag::<O>(connection, uuid)?;
+
+    if current == etag {
+        Ok(None)
+    } else {
+        Ok(Some((object, current)))
+    }
+}
+
+/// Function to insert an object into a local Redis database and read it straight back.
+/// Returns the UUID of where the object is on the database, or an error if the read differs.
+pub fn insert_and_verify<O>(
+    connection: &mut Connection,
+    object: O,
+    uuid: Option<Uuid>,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable + PartialEq + Clone,
+{
+    let uuid = insert_object_into_database(connection, object.clone(), uuid)?;
+    let stored = O::map_to_object(retrieve_object_from_database::<O>(connection, uuid)?);
+
+    if stored == object {
+        Ok(uuid)
+    } else {
+        Err(report(GlassError::VerificationFailed(uuid)))
+    }
+}
+
+/// Function to retrieve a object in a local Redis database, computing and storing it on a miss.
+/// The compute function only runs when the object isn't in the database.
+pub fn get_or_compute<O, F>(
+    connection: &mut Connection,
+    uuid: Uuid,
+    compute: F,
+) -> Result<O, GlassError>
+where
+    O: Sortable + Clone,
+    F: FnOnce() -> Result<O, GlassError>,
+{
+    operation_span!("get_or_compute", O::object_to_index(), uuid);
+
+    // A miss is handled here, so it is only reported if it is returned.
+    match unreported(|| retrieve_object_from_database::<O>(connection, uuid)) {
+        Ok(map) => Ok(O::map_to_object(map)),
+        Err(GlassError::NotIndexed(_)) => {
+            let object = compute()?;
+            insert_object_into_database(connection, object.clone(), Some(uuid))?;
+
+            Ok(object)
+        }
+        Err(e) => Err(report(e)),
+    }
+}
+
+/// Hashes a field map with FNV-1a, visiting the fields in key order so the result is stable.
+fn content_hash<T: redis::ToRedisArgs>(map: &FieldMap<T>) -> String {
+    let mut fields: Vec<(&String, &T)> = map.iter().collect();
+    fields.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for (key, value) in fields {
+        let bytes = std::iter::once(key.as_bytes().to_vec()).chain(value.to_redis_args());
+        for field in bytes {
+            // Length prefix each part so adjacent fields can't run together.
+            for byte in (field.len() as u64)
+                .to_le_bytes()
+                .iter()
+                .chain(field.iter())
+            {
+                hash ^= u64::from(*byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    format!("{:016x}", hash)
+}
+
+/// Function to request a page of objects from a local Redis database.
+/// Pages start at 1 and hold `page_size` objects each, so the last page may hold fewer.
+/// A page below 1 or a page size below 1 holds no objects.
+/// Returns the objects from the database with the key and object in a Vec.
+pub fn request_group_of_objects<O>(
+    connection: &mut Connection,
+    page: isize,
+    page_size: isize,
+) -> RedisResult<O::DataType>
+where
+    O: Sortable + Clone,
+{
+    operation_span!("request_group_of_objects", O::object_to_index());
+
+    // Negative bounds count from the end of the index, so they are never sent.
+    if page < 1 || page_size < 1 {
+        return Ok(Vec::new());
+    }
+
+    let output: Vec<String> = connection.zrange(
+        format!("{}-index", O::object_to_index()),
+        page_size * (page - 1),
+        page_size * page - 1,
+    )?;
+
+    let mut objects = Vec::with_capacity(output.len());
+    for x in output {
+        let uuid = Uuid::parse_str(&x)?;
+        let object: FieldMap<O::DataType> = retrieve_object_from_database::<O>(connection, uuid)?;
+
+        objects.push((uuid, object));
+    }
+
+    Ok(objects)
+}
+
+/// Function to request all the objects from a local Redis database.
+/// Returns the objects from the database with the key and object in a Vec.
+pub fn request_all_objects<O>(connection: &mut Connection) -> RedisResult<O::DataType>
+where
+    O: Sortable + Clone,
+{
+    operation_span!("request_all_objects", O::object_to_index());
+
+    let output: Vec<String> =
+        connection.zrange(format!("{}-index", O::object_to_index()), 0, -1)?;
+
+    let mut objects = Vec::with_capacity(output.len());
+    for x in output {
+        let uuid = Uuid::parse_str(&x)?;
+        let object: FieldMap<O::DataType> = retrieve_object_from_database::<O>(connection, uuid)?;
+
+        objects.push((uuid, object));
+    }
+
+    Ok(objects)
+}
+
+/// Function to request all the objects from a local Redis database over many connections.
+/// The index is split into one run of objects per connection and the runs are read at the
+/// same time, so the objects come back in index order like request_all_objects.
+#[cfg(feature = "parallel")]
+pub fn request_all_objects_parallel<O>(connections: &mut [Connection]) -> RedisResult<O::DataType>
+where
+    O: Sortable,
+    O::DataType: Send,
+{
+    use rayon::prelude::*;
+
+    operation_span!("request_all_objects_parallel", O::object_to_index());
+
+    let connection = connections.first_mut().ok_or_else(|| {
+        report(GlassError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "no connections given",
+        )))
+    })?;
+
+    let output: Vec<String> =
+        connection.zrange(format!("{}-index", O::object_to_index()), 0, -1)?;
+    let uuids = output
+        .iter()
+        .map(|x| Ok(Uuid::parse_str(x)?))
+        .collect::<Result<Vec<Uuid>, GlassError>>()?;
+
+    let run = uuids.len().div_ceil(connections.len()).max(1);
+    let runs: Vec<Vec<(Uuid, FieldMap<O::DataType>)>> = connections
+        .par_iter_mut()
+        .zip(uuids.par_chunks(run))
+        .map(|(connection, uuids)| {
+            uuids
+                .iter()
+                .map(|uuid| {
+                    Ok((
+                        *uuid,
+                        retrieve_object_from_database::<O>(connection, *uuid)?,
+                    ))
+                })
+                .collect()
+        })
+        .collect::<Result<_, GlassError>>()?;
+
+    Ok(runs.into_iter().flatten().collect())
+}
+
+/// Function to request all the objects from a local Redis database in pipelines.
+/// Returns the same objects as request_all_objects, with the key and object in a Vec.
+///
+/// request_all_objects takes two round trips per object, one checking its state and one
+/// reading its fields with HGETALL. This reads up to DEFAULT_MAX_PIPELINE_COMMANDS objects
+/// per round trip after the one reading the index.
+/// Objects in the index whose fields are missing fail with HashMissing.
+pub fn request_all_objects_pipelined<O>(connection: &mut Connection) -> RedisResult<O::DataType>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("request_all_objects_pipelined", index);
+
+    let output: Vec<String> = connection.zrange(format!("{}-index", index), 0, -1)?;
+    let uuids = output
+        .iter()
+        .map(|x| Ok(Uuid::parse_str(x)?))
+        .collect::<Result<Vec<Uuid>, GlassError>>()?;
+
+    let commands: Vec<redis::Cmd> = output
+        .iter()
+        .map(|x| {
+            redis::cmd("HGETALL")
+                .arg(format!("{}:{}", index, x))
+                .to_owned()
+        })
+        .collect();
+
+    let mut maps: Vec<FieldMap<O::DataType>> = Vec::with_capacity(uuids.len());
+    for pipeline in batch_pipelines(&commands, DEFAULT_MAX_PIPELINE_COMMANDS) {
+        maps.extend(pipeline.query::<Vec<FieldMap<O::DataType>>>(connection)?);
+    }
+
+    uuids
+        .into_iter()
+        .zip(maps)
+        .map(|(uuid, map)| {
+            if map.is_empty() {
+                Err(report(GlassError::HashMissing(uuid)))
+            } else {
+                Ok((uuid, map))
+            }
+        })
+        .collect()
+}
+
+/// Function to request and hydrate all the objects from a local Redis database.
+/// Objects that fail to load or hold fields that can't be read are returned with their error
+/// instead of failing the request.
+/// Index entries that aren't UUIDs are returned under the nil UUID.
+pub fn request_all_typed_lenient<O>(connection: &mut Connection) -> LenientResult<O>
+where
+    O: Sortable + Clone,
+{
+    operation_span!("request_all_typed_lenient", O::object_to_index());
+
+    let output: Vec<String> =
+        connection.zrange(format!("{}-index", O::object_to_index()), 0, -1)?;
+
+    // Failures are collected rather than returned, so none of them are reported.
+    let mut objects = Vec::with_capacity(output.len());
+    let mut failures = Vec::new();
+    unreported(|| {
+        for x in output {
+            let uuid = match Uuid::parse_str(&x) {
+                Ok(uuid) => uuid,
+                Err(e) => {
+                    failures.push((Uuid::nil(), GlassError::from(e)));
+                    continue;
+                }
+            };
+
+            match retrieve_object_from_database::<O>(connection, uuid)
+                .and_then(O::try_map_to_object)
+            {
+                Ok(object) => objects.push((uuid, object)),
+                Err(e) => failures.push((uuid, e)),
+            }
+        }
+    });
+
+    Ok((objects, failures))
+}
+
+/// Function to request the objects from a local Redis database whose field equals a value.
+/// Returns the objects from the database with the key and object in a Vec.
+pub fn request_objects_by_field<O>(
+    connection: &mut Connection,
+    field: &str,
+    value: &str,
+) -> RedisResult<O::DataType>
+where
+    O: Sortable,
+{
+    operation_span!("request_objects_by_field", O::object_to_index());
+
+    let uuids = objects_with_field_value(connection, O::object_to_index(), field, value)?;
+
+    let mut objects = Vec::with_capacity(uuids.len());
+    for uuid in uuids {
+        let object: FieldMap<O::DataType> = retrieve_object_from_database::<O>(connection, uuid)?;
+
+        objects.push((uuid, object));
+    }
+
+    Ok(objects)
+}
+
+/// Function to request the objects from a local Redis database with a tag.
+/// Reads the tag set kept up to date by inserts and removals instead of scanning the index.
+/// Returns the objects from the database with the key and object in a Vec, in index order.
+pub fn request_objects_by_tag<O>(connection: &mut Connection, tag: &str) -> RedisResult<O::DataType>
+where
+    O: Sortable,
+{
+    operation_span!("request_objects_by_tag", O::object_to_index());
+
+    Query::<O>::new().tag(tag).run(connection)
+}
+
+/// Function to request the objects whose name starts with a prefix, ignoring case.
+/// Reads the names index kept by inserts, edits and removals, so results are ordered by name.
+/// Returns at most `limit` objects, or every match when `limit` is negative.
+pub fn search_by_name_prefix<O>(
+    connection: &mut Connection,
+    prefix: &str,
+    limit: isize,
+) -> RedisResult<O::DataType>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("search_by_name_prefix", index);
+
+    // Every member starting with the prefix sorts between the prefix and the prefix followed by 0xFF.
+    let prefix = prefix.to_lowercase();
+    let mut max = format!("[{}", prefix).into_bytes();
+    max.push(0xFF);
+
+    let output: Vec<String> = redis::cmd("ZRANGEBYLEX")
+        .arg(name_key(index))
+        .arg(format!("[{}", prefix))
+        .arg(max)
+        .arg("LIMIT")
+        .arg(0)
+        .arg(limit)
+        .query(connection)
+        .map_err(type_checked(&name_key(index), "zset"))?;
+
+    let keys: Vec<&str> = output
+        .iter()
+        .filter_map(|x| x.rsplit("::").next())
+        .collect();
+    let uuids = keys
+        .iter()
+        .map(|x| Ok(Uuid::parse_str(x)?))
+        .collect::<Result<Vec<Uuid>, GlassError>>()?;
+
+    if keys.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut pipeline = redis::pipe();
+    for key in &keys {
+        pipeline.hgetall(format!("{}:{}", index, key));
+    }
+    let maps: Vec<FieldMap<O::DataType>> = pipeline.query(connection)?;
+
+    uuids
+        .into_iter()
+        .zip(maps)
+        .map(|(uuid, map)| {
+            if map.is_empty() {
+                Err(report(GlassError::HashMissing(uuid)))
+            } else {
+                Ok((uuid, map))
+            }
+        })
+        .collect()
+}
+
+/// Function to rebuild the names index of a local Redis database from the stored objects.
+/// Objects inserted before the names index existed, or left in it by older versions after a
+/// rename or soft delete, are brought in line. Returns how many names the index holds.
+pub fn rebuild_name_index<O>(connection: &mut Connection) -> Result<usize, GlassError>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("rebuild_name_index", index);
+
+    let field = match O::name_field() {
+        Some(field) => field,
+        None => return Ok(0),
+    };
+
+    let keys: Vec<String> = connection.zrange(format!("{}-index", index), 0, -1)?;
+    let mut pipeline = redis::pipe();
+    for key in &keys {
+        pipeline.hget(format!("{}:{}", index, key), field);
+    }
+    let names: Vec<Option<String>> = pipeline.query(connection)?;
+
+    let members: Vec<(i64, String)> = keys
+        .iter()
+        .zip(names)
+        .filter_map(|(key, name)| Some((0, name_member(&O::NONE_ENCODING.decode(name)?, key))))
+        .collect();
+
+    // Build the index under a temporary key and swap it in, so searches never see it half built.
+    let rebuilt = format!("{}:rebuild", name_key(index));
+    let mut pipeline = redis::pipe();
+    pipeline.atomic().del(&rebuilt).ignore();
+    if members.is_empty() {
+        pipeline.del(name_key(index)).ignore();
+    } else {
+        pipeline
+            .zadd_multiple(&rebuilt, &members)
+            .ignore()
+            .rename(&rebuilt, &name_key(index))
+            .ignore();
+    }
+    pipeline.query::<()>(connection)?;
+
+    Ok(members.len())
+}
+
+/// Finds the objects in an index whose field equals a value, in index order.
+/// Scans the field of every object, a secondary index per field could replace the scan.
+fn objects_with_field_value(
+    connection: &mut Connection,
+    index: &str,
+    field: &str,
+    value: &str,
+) -> Result<Vec<Uuid>, GlassError> {
+    let output: Vec<String> = connection.zrange(format!("{}-index", index), 0, -1)?;
+
+    // Read only the requested field of every object in one round trip.
+    let mut pipeline = redis::Pipeline::new();
+    output.iter().for_each(|x| {
+        pipeline.add_command(
+            redis::cmd("HGET")
+                .arg(format!("{}:{}", index, x))
+                .arg(field)
+                .to_owned(),
+        );
+    });
+    let values: Vec<Option<String>> = pipeline.query(connection)?;
+
+    output
+        .iter()
+        .zip(values)
+        .filter(|(_, x)| x.as_deref() == Some(value))
+        .map(|(x, _)| Ok(Uuid::parse_str(x)?))
+        .collect()
+}
+
+/// Function to insert an object into a sharded index of a local Redis database.
+/// A shard is added whenever the shards hold `shard_size` objects each, and a new object is
+/// placed in a shard picked from its UUID. The shard of each object is recorded, so objects
+/// inserted again stay in their shard as shards are added.
+/// Returns the UUID of where the object is on the database.
+pub fn insert_sharded<O>(
+    connection: &mut Connection,
+    object: O,
+    uuid: Option<Uuid>,
+    shard_size: usize,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("insert_sharded", index);
+
+    let uuid = object.primary_key().or(uuid).unwrap_or_else(Uuid::new_v4);
+    let key = uuid.to_simple().to_string();
+
+    // Place the object under WATCH, so concurrent inserts can't both grow the shards.
+    let mut shard = 0;
+    with_transaction::<(), _>(
+        connection,
+        &[shard_count_key(index), shard_map_key(index)],
+        |connection, pipeline| {
+            if let Some(placed) = connection.hget(shard_map_key(index), &key)? {
+                shard = placed;
+                return Ok(());
+            }
+
+            // Grow the shards if they are all full.
+            let mut shards = shard_count(connection, index)?;
+            let total: usize = connection.hlen(shard_map_key(index))?;
+            if total >= shards * shard_size.max(1) {
+                shards += 1;
+                pipeline.set(shard_count_key(index), shards).ignore();
+            }
+
+            shard = (uuid.as_u128() % shards as u128) as usize;
+            pipeline.hset(shard_map_key(index), &key, shard).ignore();
+            Ok(())
+        },
+    )?;
+
+    insert_object_into_index(
+        connection,
+        object,
+        Some(uuid),
+        Some(&shard_index(index, shard)),
+    )
+}
+
+/// Function to remove an object from the shard it was placed in by insert_sharded.
+/// Returns NotIndexed for objects that aren't in any shard.
+pub fn remove_sharded<O>(connection: &mut Connection, uuid: Uuid) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("remove_sharded", index, uuid);
+
+    let shard = match object_shard(connection, index, uuid)? {
+        Some(shard) => shard,
+        None => return Err(report(GlassError::NotIndexed(uuid))),
+    };
+    remove_object_from_index::<O>(connection, uuid, Some(&shard_index(index, shard)))?;
+    let _res: () = connection.hdel(shard_map_key(index), uuid.to_simple().to_string())?;
+
+    Ok(())
+}
+
+/// Function to return the shard an object was placed in by insert_sharded, if any.
+pub fn object_shard(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<Option<usize>, GlassError> {
+    Ok(connection.hget(shard_map_key(index), uuid.to_simple().to_string())?)
+}
+
+/// Function to request all the objects from every shard of a sharded index.
+/// Returns the objects from the database with the key and object in a Vec, shard by shard.
+pub fn request_all_sharded<O>(connection: &mut Connection) -> RedisResult<O::DataType>
+where
+    O: Sortable,
+{
+    let index = O::object_to_index();
+    operation_span!("request_all_sharded", index);
+
+    let mut objects = Vec::new();
+    for shard in 0..shard_count(connection, index)? {
+        let shard = shard_index(index, shard);
+        let output: Vec<String> = connection.zrange(format!("{}-index", shard), 0, -1)?;
+
+        for x in output {
+            let uuid = Uuid::parse_str(&x)?;
+            let object = retrieve_object_from_index::<O>(connection, uuid, Some(&shard))?;
+
+            objects.push((uuid, object));
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Function to return how many shards a sharded index of a local Redis database has.
+pub fn shard_count(connection: &mut Connection, index: &str) -> Result<usize, GlassError> {
+    let shards: Option<usize> = connection.get(shard_count_key(index))?;
+    Ok(shards.unwrap_or(1))
+}
+
+/// Formats the name of a shard of an index, which can be used as an index itself.
+pub fn shard_index(index: &str, shard: usize) -> String {
+    format!("{}-{}", index, shard)
+}
+
+/// Function to request a page of objects from a local Redis database ordered by score.
+/// Starts after the cursor, so pages stay stable when objects are inserted.
+/// Returns the objects and the cursor for the next page, which is None after the last page.
+/// Objects sharing a score are ordered by UUID, so none of them are skipped.
+pub fn scan_by_score(
+    connection: &mut Connection,
+    index: &str,
+    after: Option<ScanCursor>,
+    limit: isize,
+) -> ScanResult<String> {
+    operation_span!("scan_by_score", index);
+
+    let (output, cursor) = index_page_after(connection, index, after, limit)?;
+
+    let mut objects = Vec::with_capacity(output.len());
+    for uuid in output {
+        let object: FieldMap<String> =
+            connection.hgetall(format!("{}:{}", index, uuid.to_simple()))?;
+
+        objects.push((uuid, object));
+    }
+
+    Ok((objects, cursor))
+}
+
+/// Function to request a page of objects from a local Redis database after a cursor.
+/// Works like scan_by_score for the index of the object, checking each object is consistent.
+/// Scores are i64 rather than the i32 first asked for, to match scan_by_score and the index.
+pub fn request_objects_after<O>(
+    connection: &mut Connection,
+    after: Option<ScanCursor>,
+    limit: isize,
+) -> ScanResult<O::DataType>
+where
+    O: Sortable,
+{
+    operation_span!("request_objects_after", O::object_to_index());
+
+    let (output, cursor) = index_page_after(connection, O::object_to_index(), after, limit)?;
+
+    let mut objects = Vec::with_capacity(output.len());
+    for uuid in output {
+        let object = retrieve_object_from_database::<O>(connection, uuid)?;
+
+        objects.push((uuid, object));
+    }
+
+    Ok((objects, cursor))
+}
+
+/// Returns the UUIDs of at most `limit` objects after the cursor in an index,
+/// with the cursor for the next page. A negative limit returns every object after the cursor.
+fn index_page_after(
+    connection: &mut Connection,
+    index: &str,
+    after: Option<ScanCursor>,
+    limit: isize,
+) -> Result<(Vec<Uuid>, Option<ScanCursor>), GlassError> {
+    let key = format!("{}-index", index);
+
+    // Redis orders objects sharing a score by member, so only those after the cursor are kept.
+    let mut output: Vec<(String, i64)> = match after {
+        Some((score, last)) => {
+            let last = last.to_simple().to_string();
+            let tied: Vec<(String, i64)> =
+                connection.zrangebyscore_withscores(&key, score, score)?;
+            tied.into_iter().filter(|x| x.0 > last).collect()
+        }
+        None => Vec::new(),
+    };
+    if limit >= 0 {
+        output.truncate(limit as usize);
+    }
+
+    let remaining = if limit < 0 {
+        -1
+    } else {
+        limit - output.len() as isize
+    };
+    if remaining != 0 {
+        let min = after.map_or("-inf".to_owned(), |x| format!("({}", x.0));
+        let rest: Vec<(String, i64)> =
+            connection.zrangebyscore_limit_withscores(&key, min, "+inf", 0, remaining)?;
+        output.extend(rest);
+    }
+
+    let cursor = match output.last() {
+        Some(x) if output.len() as isize == limit => Some((x.1, Uuid::parse_str(&x.0)?)),
+        _ => None,
+    };
+
+    let uuids = output
+        .iter()
+        .map(|x| Uuid::parse_str(&x.0))
+        .collect::<Result<_, _>>()?;
+
+    Ok((uuids, cursor))
+}
+
+/// A change made to an object, as published to the changes channel of its index.
+#[cfg(feature = "pubsub")]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChangeOp {
+    Insert,
+    Edit,
+    Remove,
+}
+
+#[cfg(feature = "pubsub")]
+impl ChangeOp {
+    /// The name of the change used in published messages.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChangeOp::Insert => "insert",
+            ChangeOp::Edit => "edit",
+            ChangeOp::Remove => "remove",
+        }
+    }
+}
+
+/// Function to publish a change of an object to the changes channel of an index.
+/// Messages are written as `{op}:{uuid}`, see parse_change.
+#[cfg(feature = "pubsub")]
+pub fn publish_change(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+    op: ChangeOp,
+) -> Result<(), GlassError> {
+    operation_span!("publish_change", index, uuid);
+
+    Ok(change_command(index, uuid, op).query(connection)?)
+}
+
+/// Parses a message published to a changes channel.
+/// Returns None when the message isn't a change.
+#[cfg(feature = "pubsub")]
+pub fn parse_change(message: &str) -> Option<(ChangeOp, Uuid)> {
+    let mut parts = message.splitn(2, ':');
+
+    let op = match parts.next()? {
+        "insert" => ChangeOp::Insert,
+        "edit" => ChangeOp::Edit,
+        "remove" => ChangeOp::Remove,
+        _ => return None,
+    };
+
+    Some((op, Uuid::parse_str(parts.next()?).ok()?))
+}
+
+/// Returns the name of the channel changes of an index are published to.
+#[cfg(feature = "pubsub")]
+pub fn changes_channel(index: &str) -> String {
+    format!("{}:changes", index)
+}
+
+/// Returns the command publishing a change of an object.
+#[cfg(feature = "pubsub")]
+fn change_command(index: &str, uuid: Uuid, op: ChangeOp) -> redis::Cmd {
+    redis::cmd("PUBLISH")
+        .arg(changes_channel(index))
+        .arg(format!("{}:{}", op.as_str(), uuid.to_simple()))
+        .to_owned()
+}
+
+/// Function to return the current object count in a index the local Redis database.
+pub fn current_object_count(connection: &mut Connection, index: &str) -> Result<i64, GlassError> {
+    operation_span!("current_object_count", index);
 
-/// Function to return the current object count in a index the local Redis database.
-pub fn current_object_count(
-    connection: &mut Connection,
-    index: &str,
-) -> Result<i32, Box<dyn Error>> {
     Ok(connection.zcard(format!("{}-index", index))?)
 }
 
+/// Function to return the score of an object in the Redis database index.
+pub fn object_score(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<i64, GlassError> {
+    operation_span!("object_score", index, uuid);
+
+    Ok(connection.zscore(format!("{}-index", index), uuid.to_simple().to_string())?)
+}
+
+/// Function to change the score of an object in the Redis database index by a delta.
+/// Returns the new score of the object.
+pub fn change_object_score(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+    delta: i64,
+) -> Result<i64, GlassError> {
+    operation_span!("change_object_score", index, uuid);
+
+    Ok(connection.zincr(
+        format!("{}-index", index),
+        uuid.to_simple().to_string(),
+        delta,
+    )?)
+}
+
+/// Function to change the scores of many objects in the Redis database index in one round trip.
+pub fn adjust_scores(
+    connection: &mut Connection,
+    index: &str,
+    deltas: &[(Uuid, i64)],
+) -> Result<(), GlassError> {
+    operation_span!("adjust_scores", index);
+
+    // Generate a command pipeline.
+    let mut pipeline = redis::Pipeline::new();
+
+    deltas.iter().for_each(|(uuid, delta)| {
+        pipeline.add_command(
+            redis::cmd("ZINCRBY")
+                .arg(format!("{}-index", index))
+                .arg(*delta)
+                .arg(uuid.to_simple().to_string())
+                .to_owned(),
+        );
+    });
+
+    // Finally send commands to database.
+    pipeline.query::<()>(connection)?;
+
+    Ok(())
+}
+
+/// A score in the popularity set of an index.
+/// Kept apart from the index score, which only records insertion order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Score(pub i64);
+
+/// Function to change the popularity of an object by a delta, leaving its index score alone.
+/// Objects start with a popularity of 0. Returns the new popularity of the object.
+pub fn change_popularity(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+    delta: i64,
+) -> Result<Score, GlassError> {
+    operation_span!("change_popularity", index, uuid);
+
+    Ok(Score(connection.zincr(
+        popularity_key(index),
+        uuid.to_simple().to_string(),
+        delta,
+    )?))
+}
+
+/// Function to return the popularity of an object, 0 if it was never changed.
+pub fn popularity(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<Score, GlassError> {
+    operation_span!("popularity", index, uuid);
+
+    let score: Option<i64> =
+        connection.zscore(popularity_key(index), uuid.to_simple().to_string())?;
+    Ok(Score(score.unwrap_or(0)))
+}
+
+/// Function to return where an object ranks by popularity, 0 being the most popular.
+/// Returns None for objects whose popularity was never changed.
+pub fn popularity_rank(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<Option<usize>, GlassError> {
+    operation_span!("popularity_rank", index, uuid);
+
+    Ok(connection.zrevrank(popularity_key(index), uuid.to_simple().to_string())?)
+}
+
+/// Function to return the objects of an index from most to least popular.
+pub fn objects_by_popularity(
+    connection: &mut Connection,
+    index: &str,
+) -> Result<Vec<Uuid>, GlassError> {
+    operation_span!("objects_by_popularity", index);
+
+    let output: Vec<String> = connection.zrevrange(popularity_key(index), 0, -1)?;
+    output.iter().map(|x| Ok(Uuid::parse_str(x)?)).collect()
+}
+
 /// Function to return the first object in the Redis database index.
-pub fn grab_first_object(connection: &mut Connection, index: &str) -> Result<Uuid, Box<dyn Error>> {
+pub fn grab_first_object(connection: &mut Connection, index: &str) -> Result<Uuid, GlassError> {
+    operation_span!("grab_first_object", index);
+
     let output: Vec<String> = connection.zrange(format!("{}-index", index), 0, 0)?;
     let final_output = match output.first() {
         Some(s) => Uuid::parse_str(s)?,
@@ -298,7 +2477,9 @@ pub fn grab_first_object(connection: &mut Connection, index: &str) -> Result<Uui
 }
 
 /// Function to return the last object in the Redis database index.
-pub fn grab_last_object(connection: &mut Connection, index: &str) -> Result<Uuid, Box<dyn Error>> {
+pub fn grab_last_object(connection: &mut Connection, index: &str) -> Result<Uuid, GlassError> {
+    operation_span!("grab_last_object", index);
+
     let output: Vec<String> = connection.zrange(format!("{}-index", index), -1, -1)?;
     let final_output = match output.first() {
         Some(s) => Uuid::parse_str(s)?,
@@ -307,3 +2488,612 @@ pub fn grab_last_object(connection: &mut Connection, index: &str) -> Result<Uuid
 
     Ok(final_output)
 }
+
+/// Function to return how many objects in the Redis database index depend on an object.
+pub fn dependency_count(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<i64, GlassError> {
+    operation_span!("dependency_count", index, uuid);
+
+    let count: Option<i64> =
+        connection.hget(dependency_count_key(index), uuid.to_simple().to_string())?;
+    Ok(count.unwrap_or(0))
+}
+
+/// Function to fetch a mod and every mod it depends on, directly or through other mods.
+/// Returns each mod once, ordered so that every mod comes after the mods it depends on,
+/// with the root last. A dependency that isn't in the database is an error.
+pub fn resolve_dependencies(
+    connection: &mut Connection,
+    root: Uuid,
+) -> Result<Vec<(Uuid, Mod)>, GlassError> {
+    operation_span!("resolve_dependencies", Mod::object_to_index(), root);
+
+    let mut mods: Vec<(Uuid, Mod)> = Vec::new();
+    let mut pending = vec![root];
+
+    while let Some(uuid) = pending.pop() {
+        if mods.iter().any(|x| x.0 == uuid) {
+            continue;
+        }
+
+        let object: Mod = get_object(connection, uuid)?;
+        pending.extend(object.dependencies.iter().flatten().map(|x| x.0));
+        mods.push((uuid, object));
+    }
+
+    let order = topo_sort(&mods)?;
+    Ok(order
+        .into_iter()
+        .filter_map(|uuid| {
+            let position = mods.iter().position(|x| x.0 == uuid)?;
+            Some(mods.swap_remove(position))
+        })
+        .collect())
+}
+
+/// Function to return the UUIDs of every object of a type in the Redis database index.
+pub fn objects_by_type(
+    connection: &mut Connection,
+    index: &str,
+    item_type: ModType,
+) -> Result<Vec<Uuid>, GlassError> {
+    operation_span!("objects_by_type", index);
+
+    let output: Vec<String> = connection.smembers(type_set_key(index, &String::from(item_type)))?;
+
+    Ok(output
+        .iter()
+        .map(|x| Uuid::parse_str(x))
+        .collect::<Result<Vec<Uuid>, _>>()?)
+}
+
+/// Function to count how often each value of a field appears in the Redis database index.
+/// Returns the top N values with their counts, most frequent first.
+pub fn field_value_counts(
+    connection: &mut Connection,
+    index: &str,
+    field: &str,
+    top_n: usize,
+) -> Result<Vec<(String, usize)>, GlassError> {
+    operation_span!("field_value_counts", index);
+
+    let mut counts: Vec<(String, usize)> =
+        tally_field(connection, index, field)?.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts.truncate(top_n);
+
+    Ok(counts)
+}
+
+/// Function to count how many objects in the Redis database index have each value of a field.
+/// Objects without the field aren't counted.
+pub fn count_by_field<O>(
+    connection: &mut Connection,
+    field: &str,
+) -> Result<HashMap<String, i32>, GlassError>
+where
+    O: Sortable,
+{
+    operation_span!("count_by_field", O::object_to_index());
+
+    Ok(tally_field(connection, O::object_to_index(), field)?
+        .into_iter()
+        .map(|(value, count)| (value, count as i32))
+        .collect())
+}
+
+/// Function to count how many objects in the Redis database index are of each type.
+/// Returns an empty map for objects without a type field.
+pub fn count_by_item_type<O>(
+    connection: &mut Connection,
+) -> Result<HashMap<String, i32>, GlassError>
+where
+    O: Sortable,
+{
+    match O::type_field() {
+        Some(field) => count_by_field::<O>(connection, field),
+        None => Ok(HashMap::new()),
+    }
+}
+
+/// Counts each value of a field across an index.
+/// Scans the field of every object, counter keys kept up to date on write could replace the scan.
+fn tally_field(
+    connection: &mut Connection,
+    index: &str,
+    field: &str,
+) -> Result<HashMap<String, usize>, GlassError> {
+    let output: Vec<String> = connection.zrange(format!("{}-index", index), 0, -1)?;
+
+    // Read only the requested field of every object in one round trip.
+    let mut pipeline = redis::Pipeline::new();
+    output.iter().for_each(|x| {
+        pipeline.add_command(
+            redis::cmd("HMGET")
+                .arg(format!("{}:{}", index, x))
+                .arg(field)
+                .to_owned(),
+        );
+    });
+    let values: Vec<Vec<Option<String>>> = pipeline.query(connection)?;
+
+    // Tally each value.
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    values.into_iter().flatten().flatten().for_each(|value| {
+        *counts.entry(value).or_insert(0) += 1;
+    });
+
+    Ok(counts)
+}
+
+/// Checks that an object is both in the index and stored in the database.
+/// Soft deleted objects are stored without being in the index, so they are allowed.
+fn check_object_state(
+    connection: &mut Connection,
+    index: &str,
+    uuid: Uuid,
+) -> Result<(), GlassError> {
+    let state = match object_state_pipeline(index, uuid).query(connection) {
+        Ok(state) => state,
+        // Either the index or the object has the wrong type, so look up which one.
+        Err(e) if e.extension_error_code() == Some("WRONGTYPE") => {
+            let index_id = format!("{}:{}", index, uuid.to_simple());
+            let kind: String = redis::cmd("TYPE").arg(&index_id).query(connection)?;
+
+            return Err(match kind.as_str() {
+                "hash" | "none" => type_checked(&format!("{}-index", index), "zset")(e),
+                _ => type_checked(&index_id, "hash")(e),
+            });
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    object_state(uuid, state)
+}
+
+/// Maps a WRONGTYPE error on a key to TypeMismatch, passing any other error through.
+fn type_checked<'a>(
+    key: &'a str,
+    expected: &'static str,
+) -> impl FnOnce(redis::RedisError) -> GlassError + 'a {
+    move |e| match e.extension_error_code() {
+        Some("WRONGTYPE") => report(GlassError::TypeMismatch {
+            key: key.to_owned(),
+            expected,
+        }),
+        _ => e.into(),
+    }
+}
+
+/// Returns the pipeline reading the index score, existence and deleted flag of an object.
+pub(crate) fn object_state_pipeline(index: &str, uuid: Uuid) -> redis::Pipeline {
+    object_states_pipeline(index, &[uuid])
+}
+
+/// Returns the pipeline reading the state of each object, as object_state_pipeline does.
+pub(crate) fn object_states_pipeline(index: &str, uuids: &[Uuid]) -> redis::Pipeline {
+    let mut pipeline = redis::pipe();
+    for uuid in uuids {
+        let index_id = format!("{}:{}", index, uuid.to_simple());
+        pipeline
+            .zscore(format!("{}-index", index), uuid.to_simple().to_string())
+            .exists(&index_id)
+            .hexists(&index_id, DELETED_FIELD);
+    }
+    pipeline
+}
+
+/// Checks the state read by object_state_pipeline.
+pub(crate) fn object_state(
+    uuid: Uuid,
+    (score, stored, deleted): (Option<f64>, bool, bool),
+) -> Result<(), GlassError> {
+    match (score.is_some(), stored) {
+        (true, true) => Ok(()),
+        (false, false) => Err(report(GlassError::NotIndexed(uuid))),
+        (true, false) => Err(report(GlassError::HashMissing(uuid))),
+        (false, true) if deleted => Ok(()),
+        (false, true) => Err(report(GlassError::IndexOrphan(uuid))),
+    }
+}
+
+/// Returns the current time in milliseconds since the Unix epoch.
+fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_millis() as i64)
+}
+
+/// Formats the key of the set holding the UUIDs of a type in an index.
+fn type_set_key(index: &str, item_type: &str) -> String {
+    format!("{}:type:{}", index, item_type)
+}
+
+/// Formats the key counting the scores given out in an index.
+fn counter_key(index: &str) -> String {
+    format!("{}:counter", index)
+}
+
+/// Formats the key of the hash counting the dependents of each object in an index.
+fn dependency_count_key(index: &str) -> String {
+    format!("{}:dep_count", index)
+}
+
+/// Formats the key of the sorted set holding when each object in an index was last accessed.
+fn lru_key(index: &str) -> String {
+    format!("{}:lru", index)
+}
+
+/// Formats the key of the sorted set ranking the objects in an index by popularity.
+fn popularity_key(index: &str) -> String {
+    format!("{}-popularity", index)
+}
+
+/// Formats the key of the hash holding the schema version of each object in an index.
+fn schema_version_key(index: &str) -> String {
+    format!("{}:schema", index)
+}
+
+/// Formats the key of the hash holding when each field of an object was last edited.
+fn mtime_key(index: &str, uuid: Uuid) -> String {
+    format!("{}:{}:mtime", index, uuid.to_simple())
+}
+
+/// Formats the key holding how many shards an index has.
+fn shard_count_key(index: &str) -> String {
+    format!("{}:shard_count", index)
+}
+
+/// Formats the key of the hash holding the shard of each object in a sharded index.
+fn shard_map_key(index: &str) -> String {
+    format!("{}:shards", index)
+}
+
+/// Formats the key of the sorted set holding the lowercased names of the objects in an index.
+fn name_key(index: &str) -> String {
+    format!("{}:names", index)
+}
+
+/// Formats the member of the names index for an object, lowercased so searches ignore case.
+fn name_member(name: &str, key: &str) -> String {
+    format!("{}::{}", name.to_lowercase(), key)
+}
+
+/// Returns the name of an object from its field map, if it has one.
+fn stored_name<O>(field_map: &[(String, O::DataType)]) -> Option<String>
+where
+    O: Sortable,
+{
+    let field = O::name_field()?;
+    field_map
+        .iter()
+        .find(|item| item.0 == field)
+        .and_then(|item| O::NONE_ENCODING.decode(Some(field_string(&item.1))))
+}
+
+/// Formats the key of the set holding the UUIDs of a tag in an index.
+fn tag_set_key(index: &str, tag: &str) -> String {
+    format!("{}:tag:{}", index, tag)
+}
+
+/// A query over the objects in a Redis database index.
+/// Filters are intersected with the index, so results keep the index ordering.
+pub struct Query<O> {
+    tags: Vec<String>,
+    item_type: Option<ModType>,
+    page: Option<(isize, isize)>,
+    fields: Option<Vec<String>>,
+    object: PhantomData<O>,
+}
+
+impl<O> Default for Query<O>
+where
+    O: Sortable,
+{
+    fn default() -> Self {
+        Query {
+            tags: Vec::new(),
+            item_type: None,
+            page: None,
+            fields: None,
+            object: PhantomData,
+        }
+    }
+}
+
+impl<O> Query<O>
+where
+    O: Sortable,
+{
+    /// Creates a query matching every object in the index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match objects with the tag.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_owned());
+        self
+    }
+
+    /// Only match objects of the type.
+    pub fn type_(mut self, item_type: ModType) -> Self {
+        self.item_type = Some(item_type);
+        self
+    }
+
+    /// Only return a page of the matching objects, starting from page 1.
+    /// Pages or page sizes below 1 match nothing.
+    pub fn page(mut self, page: isize, page_size: isize) -> Self {
+        self.page = Some((page, page_size));
+        self
+    }
+
+    /// Only retrieve the given fields of the matching objects.
+    pub fn fields(mut self, fields: &[&str]) -> Self {
+        self.fields = Some(fields.iter().map(|x| x.to_string()).collect());
+        self
+    }
+
+    /// Runs the query against the Redis database.
+    /// Returns the matching objects from the database with the key and object in a Vec.
+    pub fn run(self, connection: &mut Connection) -> RedisResult<O::DataType> {
+        let index = O::object_to_index();
+        let index_key = format!("{}-index", index);
+
+        let (start, stop) = match self.page {
+            // Negative bounds count from the end of the index, so they are never sent.
+            Some((page, page_size)) if page < 1 || page_size < 1 => return Ok(Vec::new()),
+            Some((page, page_size)) => (page_size * (page - 1), (page_size * page) - 1),
+            None => (0, -1),
+        };
+
+        // Gather the sets the index needs to be intersected with.
+        let mut sets: Vec<String> = self.tags.iter().map(|x| tag_set_key(index, x)).collect();
+        if let Some(item_type) = self.item_type {
+            sets.push(type_set_key(index, &String::from(item_type)));
+        }
+
+        let output: Vec<String> = if sets.is_empty() {
+            connection.zrange(&index_key, start, stop)?
+        } else {
+            // Intersect into a temporary sorted set, keeping only the index scores.
+            let result_key = format!("{}:query:{}", index, Uuid::new_v4().to_simple());
+            let (output,): (Vec<String>,) = redis::pipe()
+                .atomic()
+                .cmd("ZINTERSTORE")
+                .arg(&result_key)
+                .arg(sets.len() + 1)
+                .arg(&index_key)
+                .arg(sets.as_slice())
+                .arg("WEIGHTS")
+                .arg(1)
+                .arg(vec![0; sets.len()])
+                .ignore()
+                .cmd("ZRANGE")
+                .arg(&result_key)
+                .arg(start)
+                .arg(stop)
+                .cmd("DEL")
+                .arg(&result_key)
+                .ignore()
+                .query(connection)?;
+
+            output
+        };
+
+        let mut objects = Vec::with_capacity(output.len());
+        for x in output {
+            let uuid = Uuid::parse_str(&x)?;
+            let object: FieldMap<O::DataType> = match self.fields {
+                Some(ref fields) => {
+                    let values: Vec<Option<O::DataType>> = redis::cmd("HMGET")
+                        .arg(format!("{}:{}", index, x))
+                        .arg(fields.as_slice())
+                        .query(connection)?;
+
+                    fields
+                        .iter()
+                        .cloned()
+                        .zip(values)
+                        .filter_map(|(key, value)| value.map(|y| (key, y)))
+                        .collect()
+                }
+                None => retrieve_object_from_database::<O>(connection, uuid)?,
+            };
+
+            objects.push((uuid, object));
+        }
+
+        Ok(objects)
+    }
+}
+
+/// A Redis transaction which is discarded when dropped unless it has been committed.
+/// Commands are queued on the connection with MULTI and only applied on commit.
+pub struct Transaction<'a> {
+    connection: &'a mut Connection,
+    committed: bool,
+}
+
+impl<'a> Transaction<'a> {
+    /// Starts a transaction on the connection.
+    pub fn new(connection: &'a mut Connection) -> Result<Self, GlassError> {
+        redis::cmd("MULTI").query::<()>(connection)?;
+
+        Ok(Transaction {
+            connection,
+            committed: false,
+        })
+    }
+
+    /// Queues a command to be applied when the transaction is committed.
+    pub fn queue(&mut self, command: &redis::Cmd) -> Result<(), GlassError> {
+        Ok(command.query::<()>(self.connection)?)
+    }
+
+    /// Applies every queued command, returning their results.
+    pub fn commit<T: redis::FromRedisValue>(mut self) -> Result<T, GlassError> {
+        self.committed = true;
+        Ok(redis::cmd("EXEC").query(self.connection)?)
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            // Nothing can be done about a failed discard while dropping.
+            let _res: Result<(), _> = redis::cmd("DISCARD").query(self.connection);
+        }
+    }
+}
+
+/// Runs the commands queued by a function as one atomic MULTI/EXEC pipeline.
+/// The keys are watched before the function is called, so anything it reads through the
+/// connection is still current when the pipeline runs. If a watched key changes before EXEC
+/// the pipeline is dropped and the function is called again with a fresh pipeline.
+///
+/// Watch every key the function reads, so a writer changing one of them between the read and
+/// EXEC makes the function run again with what that writer left behind. Scores for queued
+/// inserts can be taken with reserve_scores, which never gives out a score twice.
+pub fn with_transaction<T, F>(
+    connection: &mut Connection,
+    keys: &[String],
+    mut f: F,
+) -> Result<T, GlassError>
+where
+    T: redis::FromRedisValue,
+    F: FnMut(&mut Connection, &mut redis::Pipeline) -> Result<(), GlassError>,
+{
+    loop {
+        redis::cmd("WATCH").arg(keys).query::<()>(connection)?;
+
+        let mut pipeline = redis::pipe();
+        if let Err(e) = f(connection, pipeline.atomic()) {
+            redis::cmd("UNWATCH").query::<()>(connection)?;
+            return Err(e);
+        }
+
+        // EXEC returns nil when a watched key changed.
+        if let Some(result) = pipeline.query::<Option<T>>(connection)? {
+            return Ok(result);
+        }
+    }
+}
+
+/// Queues the commands inserting an object at the given score onto a pipeline.
+pub fn queue_insert<O>(pipeline: &mut redis::Pipeline, object: &O, uuid: Uuid, score: i64)
+where
+    O: Sortable,
+{
+    for command in insert_commands(object, uuid, O::object_to_index(), score) {
+        pipeline.add_command(command);
+    }
+}
+
+/// An iterator over every object in a Redis database index, ordered by score.
+/// Objects are read a page at a time with scan_by_score, holding one connection throughout.
+///
+/// When the connection drops or times out the iterator reconnects with its RedisConfig and
+/// requests the page again from the last cursor, so no object is skipped.
+/// Delivery is at least once: an object whose score changes while iterating may be seen twice.
+pub struct ScanIter {
+    config: RedisConfig,
+    connection: Connection,
+    index: String,
+    page_size: isize,
+    reconnect_attempts: usize,
+    cursor: Option<ScanCursor>,
+    page: std::vec::IntoIter<(Uuid, FieldMap<String>)>,
+    finished: bool,
+}
+
+impl ScanIter {
+    /// Connects to the database and starts iterating from the lowest score in the index.
+    pub fn new(config: RedisConfig, index: &str, page_size: isize) -> Result<Self, GlassError> {
+        Ok(ScanIter {
+            connection: config.clone().form_connection()?,
+            config,
+            index: index.to_owned(),
+            page_size,
+            reconnect_attempts: 3,
+            cursor: None,
+            page: Vec::new().into_iter(),
+            finished: false,
+        })
+    }
+
+    /// How many times to reconnect while reading a page before giving up, 3 by default.
+    pub fn reconnect_attempts(mut self, attempts: usize) -> Self {
+        self.reconnect_attempts = attempts;
+        self
+    }
+
+    /// The connection currently used by the iterator.
+    pub fn connection(&mut self) -> &mut Connection {
+        &mut self.connection
+    }
+
+    /// Reads the page after the cursor, reconnecting if the connection was lost.
+    fn next_page(&mut self) -> Result<(), GlassError> {
+        let mut attempts = 0;
+
+        loop {
+            // Errors recovered from by reconnecting aren't reported.
+            let page = unreported(|| {
+                scan_by_score(
+                    &mut self.connection,
+                    &self.index,
+                    self.cursor,
+                    self.page_size,
+                )
+            });
+            match page {
+                Ok((objects, cursor)) => {
+                    self.finished = cursor.is_none();
+                    self.cursor = cursor;
+                    self.page = objects.into_iter();
+
+                    return Ok(());
+                }
+                // Timeouts leave a reply pending on the connection, so it is replaced too.
+                Err(GlassError::Redis(e))
+                    if (!self.connection.is_open() || e.is_timeout())
+                        && attempts < self.reconnect_attempts =>
+                {
+                    attempts += 1;
+
+                    // A failed reconnect is retried until the attempts run out.
+                    if let Ok(connection) = unreported(|| self.config.clone().form_connection()) {
+                        self.connection = connection;
+                    }
+                }
+                Err(e) => return Err(report(e)),
+            }
+        }
+    }
+}
+
+impl Iterator for ScanIter {
+    type Item = Result<(Uuid, FieldMap<String>), GlassError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(object) = self.page.next() {
+                return Some(Ok(object));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if let Err(e) = self.next_page() {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Asynchronous Redis Functions
+//! These functions mirror the Redis functions using an asynchronous connection.
+//! Each function takes the connection and hands it back with the result, like `redis::aio`.
+//! Functions accept both a single `Connection` and a cloned `MultiplexedConnection`.
+
+use super::redis::RedisConfig;
+use super::redis::{
+    assign_scores, batch_pipelines, index_scores_pipeline, new_object_count, object_state,
+    object_state_pipeline, removal_commands, replacing_insert_commands, reserve_scores_pipeline,
+    stored_fields_pipeline, top_score_cmd, DEFAULT_MAX_PIPELINE_COMMANDS,
+};
+use crate::error::{report, GlassError};
+use crate::objects::Sortable;
+use futures::channel::{mpsc, oneshot};
+use futures::compat::Future01CompatExt;
+use futures::executor::block_on;
+use futures::{StreamExt, TryFutureExt};
+use redis::aio::{Connection, ConnectionLike};
+use redis::{ErrorKind, FromRedisValue, RedisError, RedisFuture, RedisResult, Value};
+use std::{collections::HashMap, thread};
+use uuid::Uuid;
+
+/// Custom Type Definitions
+type FieldMap<T> = HashMap<String, T>;
+
+/// An asynchronous connection that can be cloned and shared between tasks.
+/// Commands sent by the tasks are queued and written by a background thread, one command or
+/// pipeline at a time in the order they were sent. Unlike a pool, where each task takes a
+/// connection of its own, tasks never wait for a free connection, and an error reply only fails
+/// the pipeline it was sent in. The connection is formed again after an error.
+#[derive(Clone)]
+pub struct MultiplexedConnection {
+    sender: mpsc::UnboundedSender<Request>,
+    db: i64,
+}
+
+/// Packed commands waiting to be sent by a MultiplexedConnection.
+struct Request {
+    packed: Vec<u8>,
+    offset: usize,
+    count: usize,
+    reply: oneshot::Sender<RedisResult<Vec<Value>>>,
+}
+
+impl MultiplexedConnection {
+    /// Starts the background thread sending the commands over the connection.
+    /// The config is used to form the connection again after an error.
+    pub(crate) fn new(connection: Connection, config: RedisConfig) -> Result<Self, GlassError> {
+        let (sender, receiver) = mpsc::unbounded();
+        let db = config.database_id;
+
+        thread::Builder::new()
+            .name("glass-multiplexed".to_owned())
+            .spawn(move || block_on(drive(connection, config, receiver)))?;
+
+        Ok(MultiplexedConnection { sender, db })
+    }
+
+    /// Queues packed commands, returning the replies from `offset` on.
+    async fn send(&self, packed: Vec<u8>, offset: usize, count: usize) -> RedisResult<Vec<Value>> {
+        let (reply, receiver) = oneshot::channel();
+
+        self.sender
+            .unbounded_send(Request {
+                packed,
+                offset,
+                count,
+                reply,
+            })
+            .map_err(|_| closed_error())?;
+
+        receiver.await.map_err(|_| closed_error())?
+    }
+}
+
+impl ConnectionLike for MultiplexedConnection {
+    fn req_packed_command(self, cmd: Vec<u8>) -> RedisFuture<(Self, Value)> {
+        Box::new(
+            Box::pin(async move {
+                let mut values = self.send(cmd, 0, 1).await?;
+                Ok((self, values.remove(0)))
+            })
+            .compat(),
+        )
+    }
+
+    fn req_packed_commands(
+        self,
+        cmd: Vec<u8>,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<(Self, Vec<Value>)> {
+        Box::new(
+            Box::pin(async move {
+                let values = self.send(cmd, offset, count).await?;
+                Ok((self, values))
+            })
+            .compat(),
+        )
+    }
+
+    fn get_db(&self) -> i64 {
+        self.db
+    }
+}
+
+/// Sends the queued commands of a MultiplexedConnection until every clone is dropped.
+async fn drive(
+    connection: Connection,
+    config: RedisConfig,
+    mut receiver: mpsc::UnboundedReceiver<Request>,
+) {
+    let mut connection = Some(connection);
+
+    // Each request is sent on its own, so an error reply never fails the commands of another task.
+    while let Some(request) = receiver.next().await {
+        let current = match connection.take() {
+            Some(x) => Ok(x),
+            None => config.clone().form_async_connection().await,
+        };
+        let result = match current {
+            Ok(x) => {
+                x.req_packed_commands(request.packed, request.offset, request.count)
+                    .compat()
+                    .await
+            }
+            Err(e) => Err(RedisError::from((
+                ErrorKind::IoError,
+                "failed to reconnect",
+                e.to_string(),
+            ))),
+        };
+
+        match result {
+            Ok((next, values)) => {
+                connection = Some(next);
+                let _res = request.reply.send(Ok(values));
+            }
+            // The replies after an error are left unread, so the connection is formed again.
+            Err(e) => {
+                let _res = request.reply.send(Err(e));
+            }
+        }
+    }
+}
+
+/// The error returned once the background thread of a MultiplexedConnection has stopped.
+fn closed_error() -> RedisError {
+    RedisError::from((ErrorKind::IoError, "multiplexed connection closed"))
+}
+
+/// Sends a command to the database, returning the connection with the result.
+async fn query<C, T>(connection: C, command: &redis::Cmd) -> Result<(C, T), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    T: FromRedisValue + Send + 'static,
+{
+    Ok(command.query_async(connection).compat().await?)
+}
+
+/// Sends commands to the database in pipelines of at most `max_commands` commands.
+async fn query_in_batches<C>(
+    mut connection: C,
+    commands: &[redis::Cmd],
+    max_commands: usize,
+) -> Result<C, GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+{
+    for pipeline in batch_pipelines(commands, max_commands) {
+        let (next, ()) = pipeline.query_async(connection).compat().await?;
+        connection = next;
+    }
+
+    Ok(connection)
+}
+
+/// Takes `count` unused scores from the counter of an index, returning the first.
+async fn reserve_scores<C>(connection: C, index: &str, count: usize) -> Result<(C, i64), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+{
+    let (connection, top): (_, Vec<(String, f64)>) =
+        query(connection, &top_score_cmd(index)).await?;
+    let (connection, (last,)): (_, (i64,)) = reserve_scores_pipeline(index, &top, count)
+        .query_async(connection)
+        .compat()
+        .await?;
+
+    Ok((connection, last - count as i64 + 1))
+}
+
+/// Checks that an object is both in the index and stored in the database.
+async fn check_object_state<C>(connection: C, index: &str, uuid: Uuid) -> Result<C, GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+{
+    let (connection, state) = object_state_pipeline(index, uuid)
+        .query_async(connection)
+        .compat()
+        .await?;
+    object_state(uuid, state)?;
+
+    Ok(connection)
+}
+
+/// Function to insert an object into a local Redis database.
+/// Returns the UUID of where the object is on the database.
+pub async fn insert_object_into_database<O, C>(
+    connection: C,
+    object: O,
+    uuid: Option<Uuid>,
+) -> Result<(C, Uuid), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    O: Sortable,
+    O::DataType: Send + 'static,
+{
+    insert_objects_into_database(connection, vec![(uuid, object)])
+        .await
+        .map(|(connection, uuids)| (connection, uuids[0]))
+}
+
+/// Function to insert many objects into a local Redis database.
+/// Returns the UUIDs of where the objects are on the database, in insertion order.
+pub async fn insert_objects_into_database<O, C>(
+    connection: C,
+    objects: Vec<(Option<Uuid>, O)>,
+) -> Result<(C, Vec<Uuid>), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    O: Sortable,
+    O::DataType: Send + 'static,
+{
+    let index = O::object_to_index();
+
+    // Objects carrying their own UUID are stored under it.
+    let objects: Vec<(Option<Uuid>, O)> = objects
+        .into_iter()
+        .map(|(uuid, object)| (object.primary_key().or(uuid), object))
+        .collect();
+
+    // The nil UUID is used as the empty index sentinel, so it can't be a key.
+    if let Some(uuid) = objects.iter().find_map(|x| x.0.filter(Uuid::is_nil)) {
+        return Err(report(GlassError::InvalidKey(uuid)));
+    }
+
+    // Only objects given a UUID can already be in the index.
+    let uuids: Vec<Uuid> = objects
+        .iter()
+        .map(|x| x.0.unwrap_or_else(Uuid::new_v4))
+        .collect();
+    let given: Vec<Uuid> = objects.iter().filter_map(|x| x.0).collect();
+    let (connection, scores): (_, Vec<Option<f64>>) = index_scores_pipeline(index, &given)
+        .query_async(connection)
+        .compat()
+        .await?;
+    let indexed: Vec<Uuid> = given
+        .iter()
+        .zip(scores)
+        .filter_map(|(uuid, score)| score.map(|_| *uuid))
+        .collect();
+    let (connection, first) = match new_object_count(&uuids, &indexed) {
+        0 => (connection, 0),
+        count => reserve_scores(connection, index, count).await?,
+    };
+    let scores = assign_scores(first, &uuids, &indexed);
+
+    // Grab the fields of objects being replaced, so their entries can be taken out of the sets.
+    let (connection, stored): (_, Vec<FieldMap<O::DataType>>) =
+        stored_fields_pipeline(index, &given)
+            .query_async(connection)
+            .compat()
+            .await?;
+    let stored = given.into_iter().zip(stored).collect();
+
+    let inserts: Vec<(Uuid, &O, i64)> = objects
+        .iter()
+        .zip(&uuids)
+        .zip(scores)
+        .map(|(((_, object), uuid), score)| (*uuid, object, score))
+        .collect();
+    let commands = replacing_insert_commands(&inserts, index, stored);
+
+    // Finally send commands to database.
+    let connection = query_in_batches(connection, &commands, DEFAULT_MAX_PIPELINE_COMMANDS).await?;
+
+    Ok((connection, uuids))
+}
+
+/// Function to remove an object from a local Redis database.
+pub async fn remove_object_from_database<O, C>(connection: C, uuid: Uuid) -> Result<C, GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    O: Sortable,
+    O::DataType: Send + 'static,
+{
+    let index = O::object_to_index();
+
+    // Grab the fields of the object so its sets and counts can be updated.
+    let (connection, map) = retrieve_object_from_database::<O, _>(connection, uuid).await?;
+
+    // Finally send commands to database.
+    let commands = removal_commands::<O>(uuid, index, map);
+    query_in_batches(connection, &commands, DEFAULT_MAX_PIPELINE_COMMANDS).await
+}
+
+/// Function to check if an object is in the index of a local Redis database.
+pub async fn object_exists<O, C>(connection: C, uuid: Uuid) -> Result<(C, bool), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    O: Sortable,
+{
+    let (connection, score): (_, Option<f64>) = query(
+        connection,
+        redis::cmd("ZSCORE")
+            .arg(format!("{}-index", O::object_to_index()))
+            .arg(uuid.to_simple().to_string()),
+    )
+    .await?;
+
+    Ok((connection, score.is_some()))
+}
+
+/// Function to retrieve a object in a local Redis database.
+pub async fn retrieve_object_from_database<O, C>(
+    connection: C,
+    uuid: Uuid,
+) -> Result<(C, FieldMap<O::DataType>), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    O: Sortable,
+    O::DataType: Send + 'static,
+{
+    // Make sure the object is in a consistent state.
+    let index = O::object_to_index();
+    let connection = check_object_state(connection, index, uuid).await?;
+
+    query(
+        connection,
+        redis::cmd("HGETALL").arg(format!("{}:{}", index, uuid.to_simple())),
+    )
+    .await
+}
+
+/// Function to request all the objects from a local Redis database.
+/// Returns the objects from the database with the key and object in a Vec.
+pub async fn request_all_objects<O, C>(
+    connection: C,
+) -> Result<(C, Vec<(Uuid, FieldMap<O::DataType>)>), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+    O: Sortable,
+    O::DataType: Send + 'static,
+{
+    let (mut connection, output): (_, Vec<String>) = query(
+        connection,
+        redis::cmd("ZRANGE")
+            .arg(format!("{}-index", O::object_to_index()))
+            .arg(0)
+            .arg(-1),
+    )
+    .await?;
+
+    let mut objects = Vec::with_capacity(output.len());
+    for x in output {
+        let uuid = Uuid::parse_str(&x)?;
+        let (next, object) = retrieve_object_from_database::<O, _>(connection, uuid).await?;
+
+        connection = next;
+        objects.push((uuid, object));
+    }
+
+    Ok((connection, objects))
+}
+
+/// Function to return the current object count in a index the local Redis database.
+pub async fn current_object_count<C>(connection: C, index: &str) -> Result<(C, i64), GlassError>
+where
+    C: ConnectionLike + Send + 'static,
+{
+    query(
+        connection,
+        redis::cmd("ZCARD").arg(format!("{}-index", index)),
+    )
+    .await
+}
@@ -0,0 +1,76 @@
+//! RON Functions
+//! These functions can be used to allow an object to perform RON actions.
+//! Objects are written pretty printed so dumps are easy to edit and diff.
+//! Enum variants are written with their serde names, the same as in every other backend,
+//! so ModType::Library is written as `lib`.
+use crate::error::{report, GlassError};
+use ron::ser::PrettyConfig;
+use std::cell::Cell;
+use uuid::Uuid;
+
+thread_local! {
+    /// Whether RON is being read on this thread.
+    static READING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether RON is being read on the current thread.
+/// RON hands bare enum variants to deserialize_any as units, so fields read through
+/// deserialize_any can check this to read themselves as enums instead.
+pub fn is_reading() -> bool {
+    READING.with(Cell::get)
+}
+
+/// Convert an object that implements Serialize to a String
+pub fn object_to_string<T>(object: (Uuid, T)) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    to_pretty_string(&object)
+}
+
+/// Convert objects that implement Serialize to a String
+pub fn objects_to_string<T>(objects: &[(Uuid, T)]) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    to_pretty_string(&objects)
+}
+
+/// Convert a RON string into an object that implement Deserialize
+pub fn string_to_object<T>(string: &str) -> Result<(Uuid, T), GlassError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    from_str(string)
+}
+
+/// Convert a RON string into objects that implement Deserialize
+pub fn string_to_objects<T>(string: &str) -> Result<Vec<(Uuid, T)>, GlassError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    from_str(string)
+}
+
+/// Deserializes a value with is_reading set for the duration.
+fn from_str<T>(string: &str) -> Result<T, GlassError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let previous = READING.with(|x| x.replace(true));
+    let result = ron::from_str(string);
+    READING.with(|x| x.set(previous));
+
+    result.map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Serializes a value with one field per line and struct names left out.
+fn to_pretty_string<T>(value: &T) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    let config = PrettyConfig::new().indentor("    ".to_owned());
+
+    ron::ser::to_string_pretty(value, config)
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
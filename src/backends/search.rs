@@ -0,0 +1,162 @@
+//! Search Functions
+//! These functions keep a full text search index of objects next to the Redis database.
+//! The search index is stored on disk in its own directory and is not part of Redis,
+//! so it has to be kept in sync by using the functions in this module for every change.
+use crate::backends::redis;
+use crate::error::GlassError;
+use crate::objects::{Searchable, Sortable};
+use ::redis::Connection;
+use std::path::Path;
+use tantivy::{
+    collector::TopDocs,
+    directory::MmapDirectory,
+    doc,
+    query::QueryParser,
+    schema::{Field, Schema, Value, STORED, STRING, TEXT},
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+};
+use uuid::Uuid;
+
+/// Memory used by the index writer before it flushes to disk.
+const WRITER_MEMORY: usize = 15_000_000;
+
+/// A full text search index over the search text of objects.
+/// Folders written before the index held search text have a different schema and fail to open,
+/// so they have to be deleted and filled again.
+pub struct SearchIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: IndexWriter,
+    uuid: Field,
+    text: Field,
+}
+
+impl SearchIndex {
+    /// Opens the search index in a folder, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, GlassError> {
+        let mut builder = Schema::builder();
+        let uuid = builder.add_text_field("uuid", STRING | STORED);
+        let text = builder.add_text_field("text", TEXT);
+
+        let directory = MmapDirectory::open(path)?;
+        let index = Index::open_or_create(directory, builder.build())?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        let writer = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+
+        Ok(SearchIndex {
+            index,
+            reader,
+            writer,
+            uuid,
+            text,
+        })
+    }
+
+    /// Adds an object to the search index, replacing any previous version of it.
+    pub fn index_object<O>(&mut self, uuid: Uuid, object: &O) -> Result<(), GlassError>
+    where
+        O: Searchable,
+    {
+        let key = uuid.to_simple().to_string();
+
+        self.writer
+            .delete_term(Term::from_field_text(self.uuid, &key));
+        self.writer.add_document(doc!(
+            self.uuid => key,
+            self.text => object.search_text(),
+        ))?;
+
+        self.commit()
+    }
+
+    /// Removes an object from the search index.
+    pub fn remove_object(&mut self, uuid: Uuid) -> Result<(), GlassError> {
+        self.writer.delete_term(Term::from_field_text(
+            self.uuid,
+            &uuid.to_simple().to_string(),
+        ));
+
+        self.commit()
+    }
+
+    /// Searches the index, returning the UUIDs of the best matches with their relevance scores.
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<(Uuid, f32)>, GlassError> {
+        let parser = QueryParser::for_index(&self.index, vec![self.text]);
+        let query = parser.parse_query(query)?;
+
+        let searcher = self.reader.searcher();
+        let results = searcher.search(&query, &TopDocs::with_limit(limit))?;
+
+        results
+            .into_iter()
+            .map(|(score, address)| {
+                let document: TantivyDocument = searcher.doc(address)?;
+                let key = document
+                    .get_first(self.uuid)
+                    .and_then(|x| x.as_str())
+                    .unwrap_or_default();
+
+                Ok((Uuid::parse_str(key)?, score))
+            })
+            .collect()
+    }
+
+    /// Commits pending changes and makes them visible to searches.
+    fn commit(&mut self) -> Result<(), GlassError> {
+        self.writer.commit()?;
+        self.reader.reload()?;
+
+        Ok(())
+    }
+}
+
+/// Function to insert an object into a local Redis database and the search index.
+/// Returns the UUID of where the object is on the database.
+pub fn insert_object_into_database<O>(
+    connection: &mut Connection,
+    search: &mut SearchIndex,
+    object: O,
+    uuid: Option<Uuid>,
+) -> Result<Uuid, GlassError>
+where
+    O: Sortable + Searchable + Clone,
+{
+    let text = object.clone();
+    let uuid = redis::insert_object_into_database(connection, object, uuid)?;
+    search.index_object(uuid, &text)?;
+
+    Ok(uuid)
+}
+
+/// Function to edit a field in an object in a local Redis database and the search index.
+pub fn edit_object_from_database<O>(
+    connection: &mut Connection,
+    search: &mut SearchIndex,
+    changes: Vec<(String, O::DataType)>,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable + Searchable,
+{
+    redis::edit_object_from_database::<O>(connection, changes, uuid)?;
+
+    // Reindex the object as it is now stored.
+    let object = O::map_to_object(redis::retrieve_object_from_database::<O>(connection, uuid)?);
+    search.index_object(uuid, &object)
+}
+
+/// Function to remove an object from a local Redis database and the search index.
+pub fn remove_object_from_database<O>(
+    connection: &mut Connection,
+    search: &mut SearchIndex,
+    uuid: Uuid,
+) -> Result<(), GlassError>
+where
+    O: Sortable,
+{
+    redis::remove_object_from_database::<O>(connection, uuid)?;
+    search.remove_object(uuid)
+}
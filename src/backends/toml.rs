@@ -0,0 +1,68 @@
+//! TOML Functions
+//! These functions can be used to allow an object to perform TOML actions.
+//! TOML documents must be tables, so objects are written as an `uuid` and `object` table
+//! and lists of objects as an array of those tables under `objects`.
+use crate::error::{report, GlassError};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An object and its key, as written to TOML.
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    uuid: Uuid,
+    object: T,
+}
+
+/// A list of objects and their keys, as written to TOML.
+#[derive(Serialize, Deserialize)]
+struct Entries<T> {
+    objects: Vec<Entry<T>>,
+}
+
+/// Convert an object that implements Serialize to a String
+pub fn object_to_string<T>(object: (Uuid, T)) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    let (uuid, object) = object;
+
+    toml::to_string(&Entry { uuid, object })
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert objects that implement Serialize to a String
+pub fn objects_to_string<T>(objects: &[(Uuid, T)]) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    let objects = objects
+        .iter()
+        .map(|(uuid, object)| Entry {
+            uuid: *uuid,
+            object,
+        })
+        .collect();
+
+    toml::to_string(&Entries { objects })
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert a TOML string into an object that implement Deserialize
+pub fn string_to_object<T>(string: &str) -> Result<(Uuid, T), GlassError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    toml::from_str(string)
+        .map(|x: Entry<T>| (x.uuid, x.object))
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Convert a TOML string into objects that implement Deserialize
+pub fn string_to_objects<T>(string: &str) -> Result<Vec<(Uuid, T)>, GlassError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    toml::from_str(string)
+        .map(|x: Entries<T>| x.objects.into_iter().map(|x| (x.uuid, x.object)).collect())
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
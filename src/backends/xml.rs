@@ -0,0 +1,218 @@
+//! XML Functions
+//! These functions can be used to read and write mod manifests as XML.
+//! Each mod is written as a `<mod id="...">` element and lists of mods are wrapped in `<mods>`.
+//!
+//! Missing fields are left out, while empty strings are written as empty elements, so
+//! `<author/>` reads back as an empty author and no `<author>` element reads back as None.
+//! Dependencies are written as `<dependency id="..." version="..."/>` under `<dependencies>`
+//! and tags as `<tag>` elements under `<tags>`. Extra attributes are written as
+//! `<attribute name="...">` elements under `<extra>`, holding the value as JSON.
+use crate::error::{report, GlassError};
+use crate::objects::rainfusion::{Mod, ModDependency, ModType};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::convert::{TryFrom, TryInto};
+use uuid::Uuid;
+
+/// A mod and its key, as written to XML.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "mod")]
+struct XmlMod {
+    #[serde(rename = "@id")]
+    id: Uuid,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    name: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    author: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    summary: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    description: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_text",
+        skip_serializing_if = "Option::is_none"
+    )]
+    version: Option<String>,
+    #[serde(default)]
+    item_type: ModType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dependencies: Option<XmlDependencies>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tags: Option<XmlTags>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    extra: Option<XmlExtra>,
+}
+
+/// The dependencies of a mod, as written to XML.
+#[derive(Serialize, Deserialize)]
+struct XmlDependencies {
+    #[serde(default)]
+    dependency: Vec<XmlDependency>,
+}
+
+/// A single dependency, as written to XML.
+#[derive(Serialize, Deserialize)]
+struct XmlDependency {
+    #[serde(rename = "@id")]
+    id: Uuid,
+    #[serde(rename = "@version")]
+    version: String,
+}
+
+/// The tags of a mod, as written to XML.
+#[derive(Serialize, Deserialize)]
+struct XmlTags {
+    #[serde(default)]
+    tag: Vec<String>,
+}
+
+/// The extra attributes of a mod, as written to XML.
+#[derive(Serialize, Deserialize)]
+struct XmlExtra {
+    #[serde(default)]
+    attribute: Vec<XmlAttribute>,
+}
+
+/// A single extra attribute, with its value written as JSON.
+#[derive(Serialize, Deserialize)]
+struct XmlAttribute {
+    #[serde(rename = "@name")]
+    name: String,
+    #[serde(rename = "$text")]
+    value: String,
+}
+
+/// A list of mods, as written to XML.
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "mods")]
+struct XmlMods {
+    #[serde(rename = "mod", default)]
+    mods: Vec<XmlMod>,
+}
+
+impl From<&(Uuid, Mod)> for XmlMod {
+    fn from((uuid, object): &(Uuid, Mod)) -> Self {
+        XmlMod {
+            id: *uuid,
+            name: object.name.clone(),
+            author: object.author.clone(),
+            summary: object.summary.clone(),
+            description: object.description.clone(),
+            version: object.version.clone(),
+            item_type: object.item_type.clone(),
+            dependencies: object.dependencies.as_ref().map(|x| XmlDependencies {
+                dependency: x
+                    .iter()
+                    .map(|(id, dependency)| XmlDependency {
+                        id: *id,
+                        version: dependency.version.clone(),
+                    })
+                    .collect(),
+            }),
+            tags: object.tags.clone().map(|tag| XmlTags { tag }),
+            extra: object.extra.as_ref().map(|x| XmlExtra {
+                attribute: x
+                    .iter()
+                    .map(|(name, value)| XmlAttribute {
+                        name: name.clone(),
+                        value: value.to_string(),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<XmlMod> for (Uuid, Mod) {
+    type Error = GlassError;
+
+    fn try_from(object: XmlMod) -> Result<Self, GlassError> {
+        let extra = match object.extra {
+            Some(x) => Some(
+                x.attribute
+                    .into_iter()
+                    .map(|y| Ok((y.name, serde_json::from_str::<Value>(&y.value)?)))
+                    .collect::<Result<_, serde_json::Error>>()
+                    .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?,
+            ),
+            None => None,
+        };
+
+        Ok((
+            object.id,
+            Mod {
+                name: object.name,
+                author: object.author,
+                summary: object.summary,
+                description: object.description,
+                version: object.version,
+                item_type: object.item_type,
+                dependencies: object.dependencies.map(|x| {
+                    x.dependency
+                        .into_iter()
+                        .map(|y| (y.id, ModDependency { version: y.version }))
+                        .collect()
+                }),
+                tags: object.tags.map(|x| x.tag),
+                extra,
+            },
+        ))
+    }
+}
+
+/// Reads a text element that is present, so an empty element is an empty string, not None.
+fn deserialize_text<'de, D>(de: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    String::deserialize(de).map(Some)
+}
+
+/// Convert a mod to an XML String
+pub fn object_to_string(object: (Uuid, Mod)) -> Result<String, GlassError> {
+    quick_xml::se::to_string(&XmlMod::from(&object))
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert mods to an XML String
+pub fn objects_to_string(objects: &[(Uuid, Mod)]) -> Result<String, GlassError> {
+    let mods = XmlMods {
+        mods: objects.iter().map(XmlMod::from).collect(),
+    };
+
+    quick_xml::se::to_string(&mods).map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert an XML string into a mod
+pub fn string_to_object(string: &str) -> Result<(Uuid, Mod), GlassError> {
+    quick_xml::de::from_str::<XmlMod>(string)
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?
+        .try_into()
+}
+
+/// Convert an XML string into mods
+pub fn string_to_objects(string: &str) -> Result<Vec<(Uuid, Mod)>, GlassError> {
+    quick_xml::de::from_str::<XmlMods>(string)
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?
+        .mods
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect()
+}
@@ -1,36 +1,124 @@
 //! YAML Functions
 //! These functions can be used to allow an object to perform YAML actions.
-use std::error::Error;
+use crate::error::{report, GlassError};
+use serde_yaml::Value;
 use uuid::Uuid;
 
+/// How collections are rendered when serializing to YAML.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum YamlStyle {
+    /// Collections are written one entry per line.
+    #[default]
+    Block,
+    /// Collections are written inline, e.g. `[a, b]` and `{a: 1}`.
+    Flow,
+}
+
 /// Convert an object that implements Serialize to a String
-pub fn object_to_string<T>(object: (Uuid, T)) -> Result<String, Box<dyn Error>>
+pub fn object_to_string<T>(object: (Uuid, T)) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    object_to_string_with_style(object, YamlStyle::default())
+}
+
+/// Convert an object that implements Serialize to a String in the given style
+pub fn object_to_string_with_style<T>(
+    object: (Uuid, T),
+    style: YamlStyle,
+) -> Result<String, GlassError>
 where
     T: serde::ser::Serialize,
 {
-    Ok(serde_yaml::to_string(&object)?)
+    to_styled_string(&object, style)
 }
 
 /// Convert objects that implement Serialize to a String
-pub fn objects_to_string<T>(objects: &[(Uuid, T)]) -> Result<String, Box<dyn Error>>
+pub fn objects_to_string<T>(objects: &[(Uuid, T)]) -> Result<String, GlassError>
 where
     T: serde::ser::Serialize,
 {
-    Ok(serde_yaml::to_string(&objects)?)
+    objects_to_string_with_style(objects, YamlStyle::default())
+}
+
+/// Convert objects that implement Serialize to a String in the given style
+pub fn objects_to_string_with_style<T>(
+    objects: &[(Uuid, T)],
+    style: YamlStyle,
+) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    to_styled_string(&objects, style)
 }
 
 /// Convert a YAML string into an object that implement Deserialize
-pub fn string_to_object<T>(string: &str) -> Result<(Uuid, T), Box<dyn Error>>
+pub fn string_to_object<T>(string: &str) -> Result<(Uuid, T), GlassError>
 where
     T: serde::de::DeserializeOwned,
 {
-    Ok(serde_yaml::from_str(string)?)
+    serde_yaml::from_str(string).map_err(|e| report(GlassError::Deserialization(Box::new(e))))
 }
 
 /// Convert a YAML string into objects that implement Deserialize
-pub fn string_to_objects<T>(string: &str) -> Result<Vec<(Uuid, T)>, Box<dyn Error>>
+pub fn string_to_objects<T>(string: &str) -> Result<Vec<(Uuid, T)>, GlassError>
 where
     T: serde::de::DeserializeOwned,
 {
-    Ok(serde_yaml::from_str(string)?)
+    serde_yaml::from_str(string).map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Convert an object that implements Serialize to a String without its UUID
+pub fn object_to_string_no_key<T>(object: &T) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    serde_yaml::to_string(object).map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Convert a YAML string without a UUID into an object, pairing it with the given UUID
+pub fn string_to_object_with_key<T>(string: &str, uuid: Uuid) -> Result<(Uuid, T), GlassError>
+where
+    T: serde::de::DeserializeOwned,
+{
+    serde_yaml::from_str(string)
+        .map(|object| (uuid, object))
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))
+}
+
+/// Serializes a value in the given style.
+/// serde_yaml only writes block style, so flow style is written from the YAML value tree.
+fn to_styled_string<T>(value: &T, style: YamlStyle) -> Result<String, GlassError>
+where
+    T: serde::ser::Serialize,
+{
+    match style {
+        YamlStyle::Block => serde_yaml::to_string(value),
+        YamlStyle::Flow => serde_yaml::to_value(value)
+            .and_then(|x| flow_string(&x))
+            .map(|x| format!("---\n{}\n", x)),
+    }
+    .map_err(|e| report(GlassError::Serialization(Box::new(e))))
+}
+
+/// Writes a YAML value with every collection inline.
+fn flow_string(value: &Value) -> Result<String, serde_yaml::Error> {
+    match value {
+        Value::Sequence(items) => {
+            let items: Result<Vec<String>, _> = items.iter().map(flow_string).collect();
+            Ok(format!("[{}]", items?.join(", ")))
+        }
+        Value::Mapping(map) => {
+            let entries: Result<Vec<String>, _> = map
+                .iter()
+                .map(|(key, value)| Ok(format!("{}: {}", flow_string(key)?, flow_string(value)?)))
+                .collect();
+            Ok(format!("{{{}}}", entries?.join(", ")))
+        }
+        // Let serde_yaml handle quoting of scalars.
+        scalar => Ok(serde_yaml::to_string(scalar)?
+            .trim_start_matches("---")
+            .trim()
+            .to_owned()),
+    }
 }
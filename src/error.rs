@@ -0,0 +1,217 @@
+//! Error type returned by the glass backends.
+use std::cell::Cell;
+use std::sync::RwLock;
+use thiserror::Error;
+
+/// A function called with every error raised by a backend function.
+pub type ErrorHook = Box<dyn Fn(&GlassError) + Send + Sync>;
+
+/// The installed error hook, None until one is set.
+static ERROR_HOOK: RwLock<Option<ErrorHook>> = RwLock::new(None);
+
+thread_local! {
+    /// How many calls on this thread are keeping their errors from the hook, see unreported.
+    static HELD: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The error type for glass backend functions.
+#[derive(Error, Debug)]
+pub enum GlassError {
+    /// A connection to the Redis database could not be made.
+    #[cfg(feature = "redis_backend")]
+    #[error("failed to connect to redis: {0}")]
+    Connection(#[source] redis::RedisError),
+
+    /// An error returned by the Redis database or connection.
+    #[cfg(feature = "redis_backend")]
+    #[error("redis error: {0}")]
+    Redis(#[source] redis::RedisError),
+
+    /// A key holds a different Redis type than expected, usually written by another application.
+    #[cfg(feature = "redis_backend")]
+    #[error("key {key} is not a {expected}")]
+    TypeMismatch { key: String, expected: &'static str },
+
+    /// An object could not be serialized.
+    #[error("failed to serialize: {0}")]
+    Serialization(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// An object could not be deserialized.
+    #[error("failed to deserialize: {0}")]
+    Deserialization(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// A file could not be read.
+    #[error("io error: {0}")]
+    Io(#[source] std::io::Error),
+
+    /// An object could not be encoded with bincode.
+    #[cfg(feature = "bincode_backend")]
+    #[error("bincode error: {0}")]
+    Bincode(#[source] bincode::Error),
+
+    /// An object could not be encoded with CBOR.
+    #[cfg(feature = "cbor_backend")]
+    #[error("cbor error: {0}")]
+    Cbor(#[source] serde_cbor::Error),
+
+    /// An object could not be decoded from Protobuf.
+    #[cfg(feature = "protobuf_backend")]
+    #[error("protobuf error: {0}")]
+    Protobuf(#[source] prost::DecodeError),
+
+    /// A CSV record could not be read or written.
+    #[cfg(feature = "csv_backend")]
+    #[error("csv error: {0}")]
+    Csv(#[source] csv::Error),
+
+    /// A JSON patch could not be applied.
+    #[cfg(feature = "json_patch")]
+    #[error("json patch error: {0}")]
+    Patch(#[source] json_patch::PatchError),
+
+    /// An error returned by the search index.
+    #[cfg(feature = "search")]
+    #[error("search error: {0}")]
+    Search(#[source] tantivy::TantivyError),
+
+    /// A search query could not be parsed.
+    #[cfg(feature = "search")]
+    #[error("invalid search query: {0}")]
+    SearchQuery(#[source] tantivy::query::QueryParserError),
+
+    /// The search index folder could not be opened.
+    #[cfg(feature = "search")]
+    #[error("search index error: {0}")]
+    SearchDirectory(#[source] tantivy::directory::error::OpenDirectoryError),
+
+    /// The object does not exist in the database.
+    #[error("object {0} not found")]
+    NotFound(uuid::Uuid),
+
+    /// The object is neither in the index nor stored in the database.
+    #[error("object {0} is not indexed")]
+    NotIndexed(uuid::Uuid),
+
+    /// The object is in the index but its fields are missing from the database.
+    #[error("object {0} is indexed but its fields are missing")]
+    HashMissing(uuid::Uuid),
+
+    /// The fields of the object are stored but it is missing from the index.
+    #[error("object {0} is stored but missing from the index")]
+    IndexOrphan(uuid::Uuid),
+
+    /// The object read back after a write does not match what was written.
+    #[error("object {0} did not match after being written")]
+    VerificationFailed(uuid::Uuid),
+
+    /// The object depends on itself through its dependencies.
+    #[error("object {0} has a cyclic dependency")]
+    CyclicDependency(uuid::Uuid),
+
+    /// The UUID is reserved and can't be used as a key.
+    #[error("{0} is a reserved key")]
+    InvalidKey(uuid::Uuid),
+
+    /// A version could not be parsed as semver.
+    #[error("invalid version: {0}")]
+    Version(#[source] semver::Error),
+
+    /// The object failed validation.
+    #[error("invalid object: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+    Invalid(Vec<crate::objects::rainfusion::ValidationError>),
+
+    /// A string did not name a known item type.
+    #[error("unknown item type: {0}")]
+    UnknownType(String),
+
+    /// A stored key could not be parsed as a UUID.
+    #[error("failed to parse uuid: {0}")]
+    UuidParse(#[source] uuid::Error),
+}
+
+impl From<Vec<crate::objects::rainfusion::ValidationError>> for GlassError {
+    fn from(errors: Vec<crate::objects::rainfusion::ValidationError>) -> Self {
+        GlassError::Invalid(errors)
+    }
+}
+
+/// Sets the hook called with every error raised by a backend function, replacing the last one.
+/// Useful to report errors in one place instead of at every call. Defaults to a no-op.
+pub fn set_error_hook(hook: ErrorHook) {
+    if let Ok(mut current) = ERROR_HOOK.write() {
+        *current = Some(hook);
+    }
+}
+
+/// Passes an error to the error hook, returning it so it can be raised.
+/// Errors raised inside unreported are returned without being passed on.
+pub(crate) fn report(error: GlassError) -> GlassError {
+    if HELD.with(Cell::get) > 0 {
+        return error;
+    }
+
+    if let Ok(hook) = ERROR_HOOK.read() {
+        if let Some(ref hook) = *hook {
+            hook(&error);
+        }
+    }
+
+    error
+}
+
+/// Calls `f` without passing the errors it raises to the error hook.
+/// Used for attempts that may be retried, so only the error finally returned is reported.
+#[cfg(feature = "redis_backend")]
+pub(crate) fn unreported<T>(f: impl FnOnce() -> T) -> T {
+    /// Lets the errors through again once `f` returns or panics.
+    struct Release;
+
+    impl Drop for Release {
+        fn drop(&mut self) {
+            HELD.with(|x| x.set(x.get() - 1));
+        }
+    }
+
+    HELD.with(|x| x.set(x.get() + 1));
+    let _release = Release;
+    f()
+}
+
+/// Implements From for the variants wrapping another error, reporting the error when converted.
+/// Code that recovers from an error instead of returning it runs inside unreported.
+macro_rules! reported_from {
+    ($($(#[$meta:meta])* $source:ty => $variant:ident,)*) => {
+        $(
+            $(#[$meta])*
+            impl From<$source> for GlassError {
+                fn from(error: $source) -> Self {
+                    report(GlassError::$variant(error))
+                }
+            }
+        )*
+    };
+}
+
+reported_from! {
+    #[cfg(feature = "redis_backend")]
+    redis::RedisError => Redis,
+    std::io::Error => Io,
+    semver::Error => Version,
+    #[cfg(feature = "bincode_backend")]
+    bincode::Error => Bincode,
+    #[cfg(feature = "cbor_backend")]
+    serde_cbor::Error => Cbor,
+    #[cfg(feature = "csv_backend")]
+    csv::Error => Csv,
+    #[cfg(feature = "protobuf_backend")]
+    prost::DecodeError => Protobuf,
+    #[cfg(feature = "json_patch")]
+    json_patch::PatchError => Patch,
+    #[cfg(feature = "search")]
+    tantivy::TantivyError => Search,
+    #[cfg(feature = "search")]
+    tantivy::query::QueryParserError => SearchQuery,
+    #[cfg(feature = "search")]
+    tantivy::directory::error::OpenDirectoryError => SearchDirectory,
+    uuid::Error => UuidParse,
+}
@@ -3,5 +3,40 @@
 //! While Redis is the main database backend this library can be modified to contain any other backend.
 //! A user can choose to disable certain backends by using features available in this library.
 #![allow(unused_imports)]
+// Lets the paths generated by glass-derive resolve inside this crate.
+extern crate self as glass;
+
 pub mod backends;
+pub mod error;
 pub mod objects;
+
+/// Paths used by the code glass-derive generates, so deriving crates don't need the dependencies.
+#[doc(hidden)]
+pub mod __private {
+    pub use uuid;
+}
+
+/// Expands to the given items when glass is built with the features the field map conversions
+/// need. glass-derive wraps those items in it, since a `cfg` in generated code would check the
+/// features of the deriving crate rather than those of glass.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(any(
+    feature = "field_maps",
+    all(feature = "redis_backend", feature = "json_backend")
+))]
+macro_rules! __with_field_maps {
+    ($($items:tt)*) => { $($items)* };
+}
+
+/// Expands to the given items when glass is built with the features the field map conversions
+/// need, which it isn't.
+#[doc(hidden)]
+#[macro_export]
+#[cfg(not(any(
+    feature = "field_maps",
+    all(feature = "redis_backend", feature = "json_backend")
+)))]
+macro_rules! __with_field_maps {
+    ($($items:tt)*) => {};
+}
@@ -1,21 +1,281 @@
 //! A collection of objects for the Rainfusion website.
 pub mod rainfusion;
 
+pub use glass_derive::Sortable;
+
+use crate::error::{report, GlassError};
 use redis::{FromRedisValue, ToRedisArgs};
 use std::collections::HashMap;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How missing values of an object are written to storage.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NoneEncoding {
+    /// Missing values are stored as "N/A".
+    NA,
+    /// Missing values are stored as an empty string.
+    Empty,
+    /// Missing values are not stored at all.
+    Omit,
+}
+
+impl NoneEncoding {
+    /// Encodes an optional value, returning None when the field should not be stored.
+    pub fn encode(self, value: &Option<String>) -> Option<String> {
+        match (value, self) {
+            (Some(x), _) => Some(x.to_string()),
+            (None, NoneEncoding::NA) => Some("N/A".to_owned()),
+            (None, NoneEncoding::Empty) => Some("".to_owned()),
+            (None, NoneEncoding::Omit) => None,
+        }
+    }
+
+    /// Decodes a stored value, turning the encoded missing value back into None.
+    pub fn decode(self, value: Option<String>) -> Option<String> {
+        match (value, self) {
+            (Some(ref x), NoneEncoding::NA) if x == "N/A" => None,
+            (Some(ref x), NoneEncoding::Empty) if x.is_empty() => None,
+            (value, _) => value,
+        }
+    }
+}
+
+/// How the fields of a derived object are written into its field map.
+/// Text fields are written as they are, while fields marked `#[glass(json)]` are serialized
+/// with the codec. Set with `#[glass(codec = "...")]`, defaulting to JsonCodec.
+pub trait FieldCodec {
+    /// The type each field is stored as, which becomes the DataType of the object.
+    type DataType: FromRedisValue + ToRedisArgs + Clone;
+
+    /// Writes a text field.
+    fn encode_text(value: String) -> Self::DataType;
+
+    /// Reads a text field, returning None when the stored value isn't text.
+    fn decode_text(data: Self::DataType) -> Option<String>;
+
+    /// Writes a serialized field, returning None when the value can't be serialized.
+    fn encode_value<T: serde::Serialize>(value: &T) -> Option<Self::DataType>;
+
+    /// Reads a serialized field, returning None when the stored value can't be read.
+    fn decode_value<T: serde::de::DeserializeOwned>(data: &Self::DataType) -> Option<T>;
+}
+
+/// The prefix of the keys the entries of an extra field are stored under in the field map.
+pub const EXTRA_PREFIX: &str = "extra:";
+
+/// Stores every field as a String, with serialized fields written as JSON.
+pub struct JsonCodec;
+
+impl FieldCodec for JsonCodec {
+    type DataType = String;
+
+    fn encode_text(value: String) -> String {
+        value
+    }
+
+    fn decode_text(data: String) -> Option<String> {
+        Some(data)
+    }
+
+    fn encode_value<T: serde::Serialize>(value: &T) -> Option<String> {
+        serde_json::to_string(value).ok()
+    }
+
+    fn decode_value<T: serde::de::DeserializeOwned>(data: &String) -> Option<T> {
+        serde_json::from_str(data).ok()
+    }
+}
 
 /// A generic trait to allow objects to be used easily with
 /// database backends in glass.
 pub trait Sortable {
     type DataType: FromRedisValue + ToRedisArgs + Clone;
 
+    /// How missing values are written by object_to_map.
+    const NONE_ENCODING: NoneEncoding = NoneEncoding::NA;
+
+    /// The version of the stored layout of the object, stored alongside each object on insert.
+    /// Raise it when fields change so older objects are passed through migrate_object.
+    const SCHEMA_VERSION: u32 = 1;
+
     fn object_to_index() -> &'static str;
 
-    #[cfg(feature = "redis_backend")]
-    #[cfg(feature = "json_backend")]
+    /// The names of the stored fields, in declaration order.
+    fn fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The fields included when the object is serialized for the public.
+    /// None, the default, makes every serialized field public, including ones fields() leaves out.
+    fn public_fields() -> Option<&'static [&'static str]> {
+        None
+    }
+
+    /// The order fields are written in by object_to_map.
+    fn map_field_order() -> &'static [&'static str] {
+        Self::fields()
+    }
+
+    /// The field used to group objects into per-type sets, if any.
+    fn type_field() -> Option<&'static str> {
+        None
+    }
+
+    /// The field holding the name of the object, indexed for searches by name prefix.
+    fn name_field() -> Option<&'static str> {
+        None
+    }
+
+    /// The tags used to group objects into per-tag sets.
+    fn tag_values(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The UUIDs of the objects this object depends on, used to count dependents.
+    fn dependency_keys(&self) -> Vec<Uuid> {
+        Vec::new()
+    }
+
+    /// The UUID the object carries itself, if any.
+    /// Insert functions store the object under it instead of the UUID they are given.
+    fn primary_key(&self) -> Option<Uuid> {
+        None
+    }
+
+    /// How long stored objects live before they expire, if they expire at all.
+    /// Only the fields expire, so an expired object is reported as HashMissing until removed.
+    fn default_ttl() -> Option<Duration> {
+        None
+    }
+
+    /// Cleans up the object before it is stored by insert_normalized_object.
+    fn normalize(&mut self) -> Result<(), GlassError> {
+        Ok(())
+    }
+
+    /// Checks the object before it is stored by validated_insert.
+    fn validate(&self) -> Result<(), GlassError> {
+        Ok(())
+    }
+
+    /// Brings the fields of an object stored at an older schema version up to date.
+    /// Called by get_object before map_to_object, so missing fields can be backfilled.
+    fn migrate_object(
+        _map: &mut HashMap<String, Self::DataType>,
+        _from_version: u32,
+    ) -> Result<(), GlassError> {
+        Ok(())
+    }
+
+    /// Whether inserts, edits and removals publish to the changes channel of the index.
+    #[cfg(feature = "pubsub")]
+    fn publish_changes() -> bool {
+        false
+    }
+
+    #[cfg(any(
+        feature = "field_maps",
+        all(feature = "redis_backend", feature = "json_backend")
+    ))]
     fn map_to_object(map: HashMap<String, Self::DataType>) -> Self;
 
-    #[cfg(feature = "redis_backend")]
-    #[cfg(feature = "json_backend")]
+    #[cfg(any(
+        feature = "field_maps",
+        all(feature = "redis_backend", feature = "json_backend")
+    ))]
     fn object_to_map(&self) -> Vec<(String, Self::DataType)>;
+
+    /// Like map_to_object, but fails on fields holding values that can't be read
+    /// instead of reading them as their defaults.
+    /// Defaults to map_to_object for objects whose fields can always be read.
+    #[cfg(any(
+        feature = "field_maps",
+        all(feature = "redis_backend", feature = "json_backend")
+    ))]
+    fn try_map_to_object(map: HashMap<String, Self::DataType>) -> Result<Self, GlassError>
+    where
+        Self: Sized,
+    {
+        Ok(Self::map_to_object(map))
+    }
+
+    /// Whether the keys written by object_to_map are the same as fields().
+    /// Keys left out by NoneEncoding::Omit may be missing, any other difference means the two drifted.
+    #[cfg(any(
+        feature = "field_maps",
+        all(feature = "redis_backend", feature = "json_backend")
+    ))]
+    fn map_matches_fields(&self) -> bool {
+        let fields = Self::fields();
+        let keys: Vec<String> = self
+            .object_to_map()
+            .into_iter()
+            .map(|x| x.0)
+            .filter(|x| !x.starts_with(EXTRA_PREFIX))
+            .collect();
+
+        keys.iter().all(|x| fields.contains(&x.as_str()))
+            && (Self::NONE_ENCODING == NoneEncoding::Omit
+                || fields.iter().all(|x| keys.iter().any(|y| y == x)))
+    }
+}
+
+/// A trait for objects with text that can be fed to a search index.
+pub trait Searchable {
+    /// The indexable text fields of the object joined by spaces.
+    fn search_text(&self) -> String;
+}
+
+/// Sorts a field map into the given field order.
+/// Fields missing from the order keep their relative position after the ordered fields.
+pub fn order_fields<T>(mut map: Vec<(String, T)>, order: &[&str]) -> Vec<(String, T)> {
+    map.sort_by_key(|x| order.iter().position(|y| *y == x.0).unwrap_or(order.len()));
+    map
+}
+
+/// Converts a field map into a HashMap.
+/// When a field appears more than once the last value is kept.
+pub fn fieldmap_to_hashmap<T>(map: Vec<(String, T)>) -> HashMap<String, T> {
+    map.into_iter().collect()
+}
+
+/// Converts a HashMap into a field map ordered by the fields of the object.
+/// Fields the object doesn't declare are placed after the declared fields, sorted by name.
+pub fn hashmap_to_fieldmap<O>(map: HashMap<String, O::DataType>) -> Vec<(String, O::DataType)>
+where
+    O: Sortable,
+{
+    let mut map: Vec<(String, O::DataType)> = map.into_iter().collect();
+    map.sort_by(|a, b| a.0.cmp(&b.0));
+    order_fields(map, O::fields())
+}
+
+/// Checks that an object can be stored without writing it anywhere.
+/// Converts the object into its field map and round trips it through JSON,
+/// returning the first failure.
+#[cfg(any(
+    feature = "field_maps",
+    all(feature = "redis_backend", feature = "json_backend")
+))]
+pub fn validate_serializable<O>(object: &O) -> Result<(), GlassError>
+where
+    O: Sortable + serde::Serialize,
+{
+    if !object.map_matches_fields() {
+        return Err(report(GlassError::Serialization(
+            format!(
+                "the field map of {} does not match its fields",
+                O::object_to_index()
+            )
+            .into(),
+        )));
+    }
+
+    let string = serde_json::to_string(object)
+        .map_err(|e| report(GlassError::Serialization(Box::new(e))))?;
+    serde_json::from_str::<serde_json::Value>(&string)
+        .map_err(|e| report(GlassError::Deserialization(Box::new(e))))?;
+
+    Ok(())
 }